@@ -0,0 +1,252 @@
+use crate::hashers::{GlobalHasher, Hasher};
+use crate::stores::{AsyncNodeStore, StagedStore, StagedWrites, TreeCache};
+use crate::utils::{get_level_count, pad_leaves};
+use crate::{Hash, HashDirection, MerkleError, MerkleTree, Node, PathTrace, Proof};
+use crate::{TreeLayout, TreeMetadata};
+use rayon::prelude::*;
+use std::marker::PhantomData;
+
+/// A `MerkleTree` front-end over an `AsyncNodeStore`, (eg. for async RPC servers serving proofs)
+/// the operations of `MerkleTree` run on the blocking thread pool against `AsyncNodeStore::reads`,
+/// and their writes are committed with an awaited `AsyncNodeStore::commit`.
+/// It keeps the metadata of `MerkleTree` in the store, so either kind can reopen the other's tree,
+/// and its proofs verify with `MerkleTree::verify_proof_at`
+#[derive(Debug)]
+pub struct AsyncMerkleTree<Store: AsyncNodeStore, H: Hasher = GlobalHasher> {
+    metadata: TreeMetadata,
+    pub tree_cache: Store,
+    hasher: PhantomData<H>,
+}
+
+// constructors using the default hasher, (type parameter defaults are not used for inference)
+impl<Store: AsyncNodeStore> AsyncMerkleTree<Store> {
+    /// Constructs a padded tree from given input data
+    pub async fn construct<D, I>(input: I, store: Store) -> Result<Self, MerkleError>
+    where
+        D: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Send + 'static,
+        I: IntoIterator<Item = D>,
+    {
+        Self::construct_with_layout(input, TreeLayout::Padded, store).await
+    }
+    /// Reopens a tree persisted in `store`, see `open_with_hasher`
+    pub async fn open(store: Store) -> Result<Self, MerkleError> {
+        Self::open_with_hasher(store).await
+    }
+}
+
+impl<Store: AsyncNodeStore, H: Hasher + 'static> AsyncMerkleTree<Store, H> {
+    /// Constructs a tree from given input data, hashed with `H` and laid out according to `layout`.
+    /// The nodes are hashed a level at a time on the blocking thread pool and committed to the store
+    /// in batches of `AsyncNodeStore::batch_size`, the metadata with the last one.
+    /// A failure part way leaves nodes without metadata in the store, which `open` rejects
+    pub async fn construct_with_layout<D, I>(
+        input: I,
+        layout: TreeLayout,
+        mut store: Store,
+    ) -> Result<Self, MerkleError>
+    where
+        D: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Send + 'static,
+        I: IntoIterator<Item = D>,
+    {
+        let input: Vec<D> = input.into_iter().collect();
+        let size = input.len();
+        let mut metadata = MerkleTree::<TreeCache, H>::empty(layout, TreeCache::new()).metadata();
+        let mut nodes = tokio::task::spawn_blocking(move || {
+            let leaves = input.iter().map(|data| Node::new::<H>(data, true));
+            match layout {
+                TreeLayout::Padded => pad_leaves(leaves, size).map(|(_, leaves)| leaves.collect()),
+                TreeLayout::Unbalanced => Ok(leaves.collect::<Vec<_>>()),
+            }
+        })
+        .await
+        .map_err(MerkleError::store)??;
+        if !nodes.is_empty() {
+            metadata.leaf_count = nodes.len();
+            metadata.level_count = get_level_count(nodes.len());
+            metadata.padding_start = size - 1;
+            metadata.is_padded = nodes.len() > size;
+            // counted like `MerkleTree` does, the padding repeats the last leaf
+            metadata.unique_leaf_count = match layout {
+                TreeLayout::Padded => {
+                    1 + nodes
+                        .windows(2)
+                        .filter(|pair| pair[0].data != pair[1].data)
+                        .count()
+                }
+                TreeLayout::Unbalanced => size,
+            };
+        }
+        let batch_size = store.batch_size().max(1);
+        let mut writes = StagedWrites::default();
+        // a level at a time from the leaves up, so the lookup of a padded leaf points
+        // at the leaf rather than one of its padding copies, which appends replace
+        for level in (0..=metadata.level_count).rev() {
+            for (index, node) in nodes.iter().enumerate() {
+                let path = match level {
+                    0 => PathTrace::root(0),
+                    _ => PathTrace::new(HashDirection::from_index(index), level, index),
+                };
+                writes.nodes.insert(path, Some(*node));
+                if writes.nodes.len() == batch_size {
+                    store.commit(std::mem::take(&mut writes)).await?;
+                }
+            }
+            if level > 0 {
+                nodes = tokio::task::spawn_blocking(move || parents::<H>(&nodes))
+                    .await
+                    .map_err(MerkleError::store)?;
+            }
+        }
+        if let Some(root) = nodes.first() {
+            metadata.root = root.data;
+        }
+        writes.metadata = Some(metadata.to_bytes());
+        store.commit(writes).await?;
+        Ok(Self {
+            metadata,
+            tree_cache: store,
+            hasher: PhantomData,
+        })
+    }
+    /// Reopens a tree persisted in `store` by a `MerkleTree` or an `AsyncMerkleTree`
+    pub async fn open_with_hasher(mut store: Store) -> Result<Self, MerkleError> {
+        let bytes = store
+            .load_metadata()
+            .await?
+            .ok_or(MerkleError::MissingMetadata)?;
+        let metadata = TreeMetadata::from_bytes(&bytes)?;
+        if metadata.algorithm != H::ALGORITHM || metadata.domain_separated != H::DOMAIN_SEPARATED {
+            return Err(MerkleError::InvalidMetadata(format!(
+                "the tree was built with {}",
+                metadata.algorithm.name()
+            )));
+        }
+        store.sort().await?;
+        Ok(Self {
+            metadata,
+            tree_cache: store,
+            hasher: PhantomData,
+        })
+    }
+    pub fn root(&self) -> &Hash {
+        &self.metadata.root
+    }
+    /// the state needed to reopen this tree from its store
+    pub fn metadata(&self) -> TreeMetadata {
+        self.metadata
+    }
+    pub fn is_empty(&self) -> bool {
+        self.metadata.leaf_count == 0
+    }
+    /// number of leaves in the tree, not counting the padding of padded trees
+    pub fn len(&self) -> usize {
+        match self.metadata.layout {
+            _ if self.is_empty() => 0,
+            TreeLayout::Padded => self.metadata.padding_start + 1,
+            TreeLayout::Unbalanced => self.metadata.leaf_count,
+        }
+    }
+    /// runs `read` on a `MerkleTree` over the nodes of this tree on the blocking thread pool
+    async fn read<R, F>(&self, read: F) -> Result<R, MerkleError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&MerkleTree<Store::Reads, H>) -> Result<R, MerkleError> + Send + 'static,
+    {
+        let (metadata, reads) = (self.metadata, self.tree_cache.reads());
+        tokio::task::spawn_blocking(move || read(&MerkleTree::with_metadata(metadata, reads)))
+            .await
+            .map_err(MerkleError::store)?
+    }
+    /// runs `write` on a `MerkleTree` over the staged nodes of this tree on the blocking thread pool,
+    /// then commits its node writes and metadata at once, (see `MerkleTree::transaction`).
+    /// When `write` fails nothing is written
+    async fn write<R, F>(&mut self, write: F) -> Result<R, MerkleError>
+    where
+        R: Send + 'static,
+        F: for<'a> FnOnce(
+                &mut MerkleTree<StagedStore<'a, Store::Reads>, H>,
+            ) -> Result<R, MerkleError>
+            + Send
+            + 'static,
+    {
+        let (metadata, reads) = (self.metadata, self.tree_cache.reads());
+        let (result, metadata, writes) = tokio::task::spawn_blocking(move || {
+            let mut staged = MerkleTree::with_metadata(metadata, StagedStore::new(&reads));
+            let result = write(&mut staged)?;
+            Ok::<_, MerkleError>((result, staged.metadata(), staged.tree_cache.into_writes()))
+        })
+        .await
+        .map_err(MerkleError::store)??;
+        if !writes.is_empty() {
+            self.tree_cache.commit(writes).await?;
+            self.metadata = metadata;
+        }
+        Ok(result)
+    }
+
+    /// appends a leaf, see `append_batch`
+    pub async fn append<D: AsRef<[u8]>>(&mut self, data: &D) -> Result<(), MerkleError> {
+        self.append_batch([data]).await
+    }
+    /// appends all of `input`, growing the tree once and rehashing every changed node once,
+    /// (see `MerkleTree::try_append_batch`)
+    pub async fn append_batch<D, I>(&mut self, input: I) -> Result<(), MerkleError>
+    where
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = D>,
+    {
+        let input: Vec<Vec<u8>> = input
+            .into_iter()
+            .map(|data| data.as_ref().to_vec())
+            .collect();
+        self.write(move |tree| tree.try_append_batch(input)).await
+    }
+    /// replaces the leaf at `index` with the hash of `data`, returning the old leaf hash.
+    /// `MerkleError::LeafIndexOutOfRange` if there is no leaf at `index`
+    pub async fn update_leaf<D: AsRef<[u8]>>(
+        &mut self,
+        index: usize,
+        data: &D,
+    ) -> Result<Hash, MerkleError> {
+        let data = data.as_ref().to_vec();
+        self.write(move |tree| tree.try_update_leaf(index, &data))
+            .await
+    }
+
+    /// update the leaf holding `target_hash` with a new one, both are leaf hashes, ie. `H::hash_leaf(&data)`.
+    /// `MerkleError::HashNotFound` if no leaf holds it
+    pub async fn update(&mut self, target_hash: &Hash, new: Hash) -> Result<(), MerkleError> {
+        let target_hash = *target_hash;
+        self.write(move |tree| tree.try_update(&target_hash, new))
+            .await
+    }
+
+    /// Returns a list of hashes that can be used to prove that the given data is in this tree
+    pub async fn prove<D: AsRef<[u8]>>(&self, data: &D) -> Result<Option<Proof>, MerkleError> {
+        let data = data.as_ref().to_vec();
+        self.read(move |tree| tree.try_prove(&data)).await
+    }
+    /// Returns the proof for the leaf at `leaf_index`
+    pub async fn prove_index(&self, leaf_index: usize) -> Result<Option<Proof>, MerkleError> {
+        self.read(move |tree| tree.try_prove_index(leaf_index))
+            .await
+    }
+}
+
+/// the nodes of the level above `nodes`, the last one is promoted unchanged when it has no sibling, (RFC 6962 layout)
+fn parents<H: Hasher>(nodes: &[Node]) -> Vec<Node> {
+    nodes
+        .par_chunks(2)
+        .map(|pair| match (pair[0], pair.get(1)) {
+            (left, Some(right)) => Node {
+                data: H::hash_concat(&left.data, &right.data),
+                is_leaf: false,
+                from_duplicate: left.from_duplicate || right.from_duplicate,
+            },
+            (left, None) => Node {
+                is_leaf: false,
+                ..left
+            },
+        })
+        .collect()
+}
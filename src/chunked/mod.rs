@@ -0,0 +1,122 @@
+mod proof;
+use crate::hashers::{GlobalHasher, Hasher};
+use crate::{MerkleError, MerkleTree, Node, NodeStore, TreeLayout};
+pub use proof::*;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::path::Path;
+
+/// number of chunks read before they are hashed in parallel
+const CHUNKS_PER_BATCH: usize = 256;
+
+/// A Merkle tree over the fixed-size chunks of a file or any other reader, (as in BLAKE3/Bao)
+/// so a byte range can be proven with only the chunks it spans.
+/// Every chunk is a leaf, the last one can be shorter
+#[derive(Debug)]
+pub struct ChunkedTree<Store: NodeStore, H: Hasher = GlobalHasher> {
+    pub tree: MerkleTree<Store, H>,
+    chunk_size: usize,
+    byte_len: u64,
+}
+
+// constructors using the default hasher, (type parameter defaults are not used for inference)
+impl<Store: NodeStore + Send> ChunkedTree<Store> {
+    /// Builds a padded tree over the chunks of `reader`, eg. `ChunkedTree::from_reader(file, 64 * 1024, store)`
+    pub fn from_reader<R: Read>(
+        reader: R,
+        chunk_size: usize,
+        store: Store,
+    ) -> Result<Self, MerkleError> {
+        Self::from_reader_with_layout(reader, chunk_size, TreeLayout::Padded, store)
+    }
+    /// Builds a padded tree over the chunks of the file at `path`
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        chunk_size: usize,
+        store: Store,
+    ) -> Result<Self, MerkleError> {
+        Self::from_path_with_layout(path, chunk_size, TreeLayout::Padded, store)
+    }
+}
+
+impl<Store: NodeStore + Send, H: Hasher> ChunkedTree<Store, H> {
+    /// Builds a tree over the chunks of `reader`, hashed with `H` and laid out according to `layout`.
+    /// Only the chunk hashes are kept while reading, not the chunks
+    pub fn from_reader_with_layout<R: Read>(
+        mut reader: R,
+        chunk_size: usize,
+        layout: TreeLayout,
+        store: Store,
+    ) -> Result<Self, MerkleError> {
+        if chunk_size == 0 {
+            let error = io::Error::new(ErrorKind::InvalidInput, "the chunk size is zero");
+            return Err(error.into());
+        }
+        let mut leaves = vec![];
+        let mut byte_len = 0;
+        loop {
+            let mut batch = Vec::with_capacity(CHUNKS_PER_BATCH);
+            while batch.len() < CHUNKS_PER_BATCH {
+                let mut chunk = vec![0; chunk_size];
+                let read = read_chunk(&mut reader, &mut chunk)?;
+                if read == 0 {
+                    break;
+                }
+                chunk.truncate(read);
+                byte_len += read as u64;
+                batch.push(chunk);
+            }
+            let done = batch.len() < CHUNKS_PER_BATCH;
+            leaves.par_extend(
+                batch
+                    .into_par_iter()
+                    .map(|chunk| Node::new::<H>(chunk, true)),
+            );
+            if done {
+                break;
+            }
+        }
+        let size_hint = leaves.len();
+        let tree = MerkleTree::try_from_leaves(leaves.into_iter(), size_hint, layout, store)?;
+        Ok(Self {
+            tree,
+            chunk_size,
+            byte_len,
+        })
+    }
+    pub fn from_path_with_layout<P: AsRef<Path>>(
+        path: P,
+        chunk_size: usize,
+        layout: TreeLayout,
+        store: Store,
+    ) -> Result<Self, MerkleError> {
+        let file = BufReader::new(File::open(path)?);
+        Self::from_reader_with_layout(file, chunk_size, layout, store)
+    }
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+    /// length of the input in bytes
+    pub fn byte_len(&self) -> u64 {
+        self.byte_len
+    }
+    /// number of chunks, (without padding)
+    pub fn chunk_count(&self) -> usize {
+        self.byte_len.div_ceil(self.chunk_size as u64) as usize
+    }
+}
+
+/// fills `chunk` from `reader` unless it ends first, returns the number of bytes read
+fn read_chunk<R: Read>(reader: &mut R, chunk: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < chunk.len() {
+        match reader.read(&mut chunk[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(filled)
+}
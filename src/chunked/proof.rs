@@ -0,0 +1,96 @@
+use super::ChunkedTree;
+use crate::hashers::Hasher;
+use crate::{Hash, MerkleTree};
+use crate::{MultiProof, NodeStore, TreeLayout};
+use std::ops::Range;
+
+/// Proves a byte range of the input of a `ChunkedTree` with the chunks it spans
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RangeProof {
+    /// the proven bytes
+    pub range: Range<u64>,
+    pub chunk_size: usize,
+    /// length of the whole input in bytes
+    pub byte_len: u64,
+    pub layout: TreeLayout,
+    /// proves the spanned chunks, (see `chunk_span`)
+    pub proof: MultiProof,
+}
+
+impl RangeProof {
+    /// the indexes of the chunks the range spans
+    pub fn chunks(&self) -> Range<usize> {
+        let chunk_size = self.chunk_size as u64;
+        let first = self.range.start / chunk_size;
+        let last = self.range.end.saturating_sub(1) / chunk_size;
+        first as usize..last as usize + 1
+    }
+    /// the bytes of the spanned chunks, which have to be passed to `verify_range`
+    pub fn chunk_span(&self) -> Range<u64> {
+        let chunk_size = self.chunk_size as u64;
+        let chunks = self.chunks();
+        let end = (chunks.end as u64 * chunk_size).min(self.byte_len);
+        chunks.start as u64 * chunk_size..end
+    }
+    /// the number of leaves of a tree built from the whole input, including the padding
+    fn leaf_count(&self) -> usize {
+        let chunk_count = self.byte_len.div_ceil(self.chunk_size as u64) as usize;
+        match self.layout {
+            TreeLayout::Padded => chunk_count.next_power_of_two(),
+            TreeLayout::Unbalanced => chunk_count,
+        }
+    }
+}
+
+impl<Store: NodeStore + Send, H: Hasher> ChunkedTree<Store, H> {
+    /// Returns the proof of the chunks spanned by the bytes in `range`,
+    /// `None` if the range is empty or goes past the end of the input
+    pub fn prove_range(&self, range: Range<u64>) -> Option<RangeProof> {
+        if range.is_empty() || range.end > self.byte_len {
+            return None;
+        }
+        let mut proof = RangeProof {
+            range,
+            chunk_size: self.chunk_size,
+            byte_len: self.byte_len,
+            layout: self.tree.layout(),
+            proof: MultiProof::default(),
+        };
+        proof.proof = self.tree.multiproof_of(proof.chunks().collect())?;
+        Some(proof)
+    }
+    /// Verifies that `chunks`, the bytes of `proof.chunk_span()`, are at that position
+    /// of the input of the chunked tree with the given root, `chunk_size` and `byte_len`.
+    /// The proven range is at `proof.range.start - proof.chunk_span().start` in `chunks`.
+    /// The sizes have to be trusted ones, the chunk positions are derived from them, and with
+    /// the unbalanced layout a chunk can be proven at another position of a shorter input
+    pub fn verify_range(
+        chunk_size: usize,
+        byte_len: u64,
+        chunks: &[u8],
+        proof: &RangeProof,
+        root_hash: &Hash,
+    ) -> bool {
+        if chunk_size == 0
+            || proof.chunk_size != chunk_size
+            || proof.byte_len != byte_len
+            || proof.range.is_empty()
+            || proof.range.end > byte_len
+        {
+            return false;
+        }
+        let span = proof.chunk_span();
+        if chunks.len() as u64 != span.end - span.start
+            || !proof.proof.leaf_indexes.iter().copied().eq(proof.chunks())
+        {
+            return false;
+        }
+        let chunks: Vec<_> = chunks.chunks(proof.chunk_size).collect();
+        MerkleTree::<Store, H>::verify_multiproof(
+            proof.leaf_count(),
+            &chunks,
+            &proof.proof,
+            root_hash,
+        )
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{Hash, ProofEncodingError};
+use std::error::Error;
+use std::fmt;
+
+/// Errors from building, changing or reading a tree
+#[derive(Debug)]
+pub enum MerkleError {
+    /// the input ended before the given size hint
+    InputShorterThanSizeHint(usize),
+    /// no node in the tree has the given hash
+    HashNotFound(Hash),
+    /// the tree has no leaf at the given index
+    LeafIndexOutOfRange(usize),
+    /// the node store failed to read or write, (eg. a full disk)
+    Store(Box<dyn Error + Send + Sync>),
+    /// a key or node couldn't be (de)serialized for the node store
+    Serialization(Box<dyn Error + Send + Sync>),
+    ProofEncoding(ProofEncodingError),
+    /// the store has no tree metadata to open a tree from
+    MissingMetadata,
+    /// the stored tree metadata is corrupt or belongs to a different kind of tree
+    InvalidMetadata(String),
+    /// reading the input failed, (eg. a file being chunked)
+    Io(std::io::Error),
+}
+
+impl MerkleError {
+    pub fn store(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self::Store(error.into())
+    }
+    pub fn serialization(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self::Serialization(error.into())
+    }
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputShorterThanSizeHint(size_hint) => {
+                write!(f, "the input has fewer than {size_hint} items")
+            }
+            Self::HashNotFound(hash) => write!(f, "no node with hash {}", hex::encode(hash)),
+            Self::LeafIndexOutOfRange(index) => write!(f, "no leaf at index {index}"),
+            Self::Store(error) => write!(f, "node store error: {error}"),
+            Self::Serialization(error) => write!(f, "node serialization error: {error}"),
+            Self::ProofEncoding(error) => write!(f, "{error}"),
+            Self::MissingMetadata => write!(f, "the store holds no tree metadata"),
+            Self::InvalidMetadata(reason) => write!(f, "invalid tree metadata: {reason}"),
+            Self::Io(error) => write!(f, "failed to read the input: {error}"),
+        }
+    }
+}
+
+impl Error for MerkleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Store(error) | Self::Serialization(error) => Some(error.as_ref()),
+            Self::ProofEncoding(error) => Some(error),
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProofEncodingError> for MerkleError {
+    fn from(error: ProofEncodingError) -> Self {
+        Self::ProofEncoding(error)
+    }
+}
+
+impl From<std::io::Error> for MerkleError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
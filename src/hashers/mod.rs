@@ -4,18 +4,85 @@ mod sha2_hasher;
 
 #[cfg(feature = "blake3")]
 mod blake3_hasher;
+mod rfc6962;
 #[cfg(feature = "sha1")]
 mod sha1_hasher;
 #[cfg(feature = "sha3")]
 mod sha3_hasher;
-pub trait Hasher: Send + Clone + Copy {
+#[cfg(feature = "blake3")]
+pub use blake3_hasher::Blake3;
+pub use rfc6962::Rfc6962;
+#[cfg(feature = "sha1")]
+pub use sha1_hasher::Sha1Hasher;
+#[cfg(feature = "sha2")]
+pub use sha2_hasher::Sha2Hasher;
+#[cfg(feature = "sha3")]
+pub use sha3_hasher::KeccakHasher;
+/// Identifies the hash function behind a `Hasher`, (stored in encoded proofs)
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, std::hash::Hash)]
+pub enum HashAlgorithm {
+    /// a hasher implemented outside of this crate
+    #[default]
+    Custom = 0,
+    Sha256 = 1,
+    Sha1 = 2,
+    Sha3_256 = 3,
+    Blake3 = 4,
+}
+impl HashAlgorithm {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Custom),
+            1 => Some(Self::Sha256),
+            2 => Some(Self::Sha1),
+            3 => Some(Self::Sha3_256),
+            4 => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Custom => "custom",
+            Self::Sha256 => "sha256",
+            Self::Sha1 => "sha1",
+            Self::Sha3_256 => "sha3-256",
+            Self::Blake3 => "blake3",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Custom,
+            Self::Sha256,
+            Self::Sha1,
+            Self::Sha3_256,
+            Self::Blake3,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.name() == name)
+    }
+}
+pub trait Hasher: Send + Sync + Clone + Copy {
+    /// the hash function, recorded in proofs so verifiers can pick a matching hasher
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Custom;
+    /// whether leaves and interior nodes are hashed with different prefixes, (see `Rfc6962`)
+    const DOMAIN_SEPARATED: bool = false;
+
     // ------------------------- UTILITY FUNCTIONS --------------------------------------------------
     fn hash_data<T: AsRef<[u8]>>(data: &T) -> Hash;
 
+    /// hash of a leaf's data, (no prefix by default)
+    fn hash_leaf<T: AsRef<[u8]>>(data: &T) -> Hash {
+        Self::hash_data(data)
+    }
+
+    /// hash of an interior node from its children
     fn hash_concat<T: AsRef<[u8]>>(h1: &T, h2: &T) -> Hash {
         Self::hash_data(&[h1.as_ref(), h2.as_ref()].concat())
     }
 }
+/// The default hasher, picked by feature priority (sha2 > blake3 > sha1 > sha3).
+/// Any other enabled hasher can still be used through `MerkleTree<Store, H>`.
 #[cfg(feature = "sha2")]
 pub type GlobalHasher = sha2_hasher::Sha2Hasher;
 #[cfg(all(feature = "blake3", not(feature = "sha2")))]
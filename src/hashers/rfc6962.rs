@@ -0,0 +1,32 @@
+use super::{GlobalHasher, HashAlgorithm, Hasher};
+use crate::Hash;
+use std::marker::PhantomData;
+/// Domain separated hashing as defined by RFC 6962 (Certificate Transparency),
+/// leaves are hashed as `H(0x00 || data)` and interior nodes as `H(0x01 || left || right)`,
+/// so an interior node can never be passed off as a leaf (second-preimage attack).
+///
+/// Opt-in by using it as the tree's hasher, eg. `MerkleTree<TreeCache, Rfc6962<Sha2Hasher>>`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rfc6962<H: Hasher = GlobalHasher>(PhantomData<H>);
+
+impl<H: Hasher> Rfc6962<H> {
+    pub const LEAF_PREFIX: u8 = 0x00;
+    pub const NODE_PREFIX: u8 = 0x01;
+}
+
+impl<H: Hasher> Hasher for Rfc6962<H> {
+    const ALGORITHM: HashAlgorithm = H::ALGORITHM;
+    const DOMAIN_SEPARATED: bool = true;
+
+    fn hash_data<T: AsRef<[u8]>>(data: &T) -> Hash {
+        H::hash_data(data)
+    }
+
+    fn hash_leaf<T: AsRef<[u8]>>(data: &T) -> Hash {
+        H::hash_data(&[&[Self::LEAF_PREFIX], data.as_ref()].concat())
+    }
+
+    fn hash_concat<T: AsRef<[u8]>>(h1: &T, h2: &T) -> Hash {
+        H::hash_data(&[&[Self::NODE_PREFIX], h1.as_ref(), h2.as_ref()].concat())
+    }
+}
@@ -1,8 +1,10 @@
-use crate::{Hash, Hasher};
+use crate::{hashers::HashAlgorithm, Hash, Hasher};
 use sha1::{Digest, Sha1};
 #[derive(Debug, Clone, Copy)]
 pub struct Sha1Hasher;
 impl Hasher for Sha1Hasher {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha1;
+
     fn hash_data<T: AsRef<[u8]>>(data: &T) -> Hash {
         let mut output = [0; 32];
         let mut hasher = Sha1::new();
@@ -0,0 +1,130 @@
+use crate::hashers::Hasher;
+use crate::stores::StagedStore;
+use crate::{MerkleError, MerkleTree, Node, NodeStore, PathTrace};
+use std::collections::{HashMap, HashSet};
+
+/// The inconsistencies found by `MerkleTree::check_integrity`, each list sorted by path
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// interior nodes that don't hash to their children, (or the root in the metadata)
+    /// a corrupted node is reported with its parent
+    pub mismatched: Vec<PathTrace>,
+    /// nodes of the tree described by the metadata missing from the store
+    pub missing: Vec<PathTrace>,
+    /// nodes in the store outside of the tree
+    pub unexpected: Vec<PathTrace>,
+    /// leaves whose hash leads to a node without it in the lookup index.
+    /// A missing lookup isn't reported, a store keeps one per hash so repeated data loses it
+    /// with the copy it pointed at, and such leaves are found by scanning, (see `MerkleTree::prove`)
+    pub stale_lookups: Vec<PathTrace>,
+}
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty()
+            && self.missing.is_empty()
+            && self.unexpected.is_empty()
+            && self.stale_lookups.is_empty()
+    }
+}
+
+impl<Store: NodeStore + Send + Sync, H: Hasher> MerkleTree<Store, H> {
+    /// Checks the nodes in the store against the metadata, eg. after an unclean shutdown of a persisted store.
+    /// Every interior node is recomputed from its children with `hash_concat`,
+    /// and the lookup of every leaf hash has to lead to a node with it.
+    /// Lookups of hashes that aren't in the tree anymore can't be found from the nodes, so they aren't checked
+    pub fn check_integrity(&self) -> Result<IntegrityReport, MerkleError> {
+        let nodes: HashMap<PathTrace, Node> = self.tree_cache.entries().collect();
+        let mut report = IntegrityReport::default();
+        let mut expected = HashSet::with_capacity(nodes.len());
+        let mut width = self.leaf_count;
+        for level in (self.lowest_level..=self.level_count).rev() {
+            if width == 0 {
+                break;
+            }
+            for index in 0..width {
+                let path = self.path_at(level, index);
+                expected.insert(path);
+                let Some(node) = nodes.get(&path) else {
+                    report.missing.push(path);
+                    continue;
+                };
+                let is_root = level == self.lowest_level;
+                if is_root && node.data != self.root {
+                    report.mismatched.push(path);
+                    continue;
+                }
+                if level == self.level_count {
+                    continue;
+                }
+                // the children of the root are the first two nodes of the level below
+                let left = if is_root { 0 } else { index * 2 };
+                let children = (
+                    nodes.get(&self.path_at(level + 1, left)),
+                    nodes.get(&self.path_at(level + 1, left + 1)),
+                );
+                let hash = match children {
+                    (Some(left), Some(right)) => H::hash_concat(&left.data, &right.data),
+                    // a promoted node, (unbalanced layout)
+                    (Some(left), None) => left.data,
+                    // missing children are reported on their own
+                    _ => continue,
+                };
+                if hash != node.data {
+                    report.mismatched.push(path);
+                }
+            }
+            width = width.div_ceil(2);
+        }
+        for (path, node) in &nodes {
+            if !expected.contains(path) {
+                report.unexpected.push(*path);
+            }
+            if path.level != self.level_count {
+                continue;
+            }
+            let stale = self
+                .tree_cache
+                .try_get_key_by_hash(&node.data)?
+                .is_some_and(|key| nodes.get(&key).is_none_or(|found| found.data != node.data));
+            if stale {
+                report.stale_lookups.push(*path);
+            }
+        }
+        report.mismatched.sort();
+        report.missing.sort();
+        report.unexpected.sort();
+        report.stale_lookups.sort();
+        Ok(report)
+    }
+    /// Rebuilds the interior levels and the lookup index from the leaves, removing the nodes outside of the tree.
+    /// The rebuilt nodes are committed at once, (see `NodeStore::try_commit`) and the tree is laid out as
+    /// if it was constructed from its leaves. The root is recomputed, so compare it with a trusted one
+    /// to tell whether the leaves themselves were intact
+    pub fn repair(&mut self) -> Result<(), MerkleError> {
+        // the padding is rebuilt from the last leaf
+        let size = match self.is_padded {
+            true => self.padding_start + 1,
+            false => self.leaf_count,
+        };
+        let leaves = (0..size)
+            .map(|index| {
+                let path = self.path_at(self.level_count, index);
+                self.tree_cache
+                    .try_get(&path)?
+                    .ok_or_else(|| MerkleError::store(format!("the tree is missing leaf {path:?}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (metadata, writes) = {
+            let mut store = StagedStore::new(&self.tree_cache);
+            for (path, _) in self.tree_cache.entries() {
+                store.try_remove_node(path)?;
+            }
+            let tree =
+                MerkleTree::<_, H>::try_from_leaves(leaves.into_iter(), size, self.layout, store)?;
+            (tree.metadata(), tree.tree_cache.into_writes())
+        };
+        self.tree_cache.try_commit(writes)?;
+        self.set_metadata(metadata);
+        Ok(())
+    }
+}
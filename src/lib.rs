@@ -51,15 +51,33 @@ Exercise 3 (Hard):
 
  */
 
+#[cfg(feature = "async")]
+pub mod async_tree;
+pub mod chunked;
+mod error;
 pub mod hashers;
+mod integrity;
+pub mod mmr;
+pub mod proofs;
+pub mod shared;
+pub mod sparse;
 pub mod stores;
+mod transaction;
 pub mod utils;
+pub use error::MerkleError;
 use hashers::{GlobalHasher, Hasher};
-use std::collections::BTreeMap;
+pub use integrity::IntegrityReport;
+use itertools::Itertools;
+pub use proofs::*;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
 pub use stores::NodeStore;
 pub use utils::*;
+/// A Merkle tree over `Store`, hashed with `H` (the feature-selected `GlobalHasher` by default).
+/// Trees with different hashers can coexist, eg. `MerkleTree<TreeCache, Blake3>` next to `MerkleTree<TreeCache>`
 #[derive(Debug)]
-pub struct MerkleTree<Store: NodeStore> {
+pub struct MerkleTree<Store: NodeStore, H: Hasher = GlobalHasher> {
     root: Hash,
     is_padded: bool,
     pub leaf_count: usize,
@@ -69,16 +87,78 @@ pub struct MerkleTree<Store: NodeStore> {
     padding_start: usize,
     // for faster lookup , (level, Direction, index), eg. for H2, (depthlength, Right, index)
     pub tree_cache: Store, // O(1) - path look ups, O(n) search by hash
+    layout: TreeLayout,
+    hasher: PhantomData<H>,
 }
 
+// constructors using the default hasher, (type parameter defaults are not used for inference)
 impl<Store: NodeStore + Send> MerkleTree<Store> {
+    /// Constructs a Merkle tree from given input data
+    pub fn construct<B, I, U>(input: I, store: Store) -> Self
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+        I: IntoIterator<IntoIter = U>,
+        U: Iterator<Item = B>,
+    {
+        Self::construct_with_hasher(input, store)
+    }
+    pub fn from_iter<B, I>(input: I, size_hint: usize, store: Store) -> Self
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Sized,
+        I: Iterator<Item = B>,
+    {
+        Self::from_iter_with_hasher(input, size_hint, store)
+    }
+    /// Constructs a Merkle tree from given input data, returning store failures and invalid input as errors
+    pub fn try_construct<B, I, U>(input: I, store: Store) -> Result<Self, MerkleError>
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+        I: IntoIterator<IntoIter = U>,
+        U: Iterator<Item = B>,
+    {
+        Self::try_construct_with_layout(input, TreeLayout::Padded, store)
+    }
+    pub fn try_from_iter<B, I>(
+        input: I,
+        size_hint: usize,
+        store: Store,
+    ) -> Result<Self, MerkleError>
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Sized,
+        I: Iterator<Item = B>,
+    {
+        Self::try_from_iter_with_layout(input, size_hint, TreeLayout::Padded, store)
+    }
+
+    /// Verifies that the given input data produces the given root hash
+    pub fn verify<D: AsRef<[u8]> + Eq + Clone + std::hash::Hash, I, U, B>(
+        input: I,
+        root_hash: &D,
+        store: Store,
+    ) -> bool
+    where
+        I: IntoIterator<IntoIter = U>,
+        U: Iterator<Item = B> + Clone,
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+    {
+        Self::verify_with_hasher(input, root_hash, store)
+    }
+
+    /// Reopens a tree persisted in `store`, see `open_with_hasher`
+    pub fn open(store: Store) -> Result<Self, MerkleError> {
+        Self::open_with_hasher(store)
+    }
+}
+
+impl<Store: NodeStore + Send, H: Hasher> MerkleTree<Store, H> {
     /// Gets root hash for this tree
     pub fn root(&self) -> &Hash {
         &self.root
     }
 
-    /// Constructs a Merkle tree from given input data
-    pub fn construct<B, I, U>(input: I, store: Store) -> Self
+    /// Constructs a Merkle tree from given input data, hashed with `H`.
+    /// eg. `MerkleTree::<_, Blake3>::construct_with_hasher(&data, store)`
+    pub fn construct_with_hasher<B, I, U>(input: I, store: Store) -> Self
     where
         B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
         I: IntoIterator<IntoIter = U>,
@@ -86,27 +166,116 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
     {
         let input = input.into_iter();
         let size_hint = input.size_hint().1.unwrap_or_default();
-        Self::from_iter(input, size_hint, store)
+        Self::from_iter_with_hasher(input, size_hint, store)
     }
-    pub fn from_iter<B, I>(input: I, size_hint: usize, store: Store) -> Self
+    pub fn from_iter_with_hasher<B, I>(input: I, size_hint: usize, store: Store) -> Self
     where
         B: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Sized,
         I: Iterator<Item = B>,
     {
-        let is_padded = !size_hint.is_power_of_two();
-        let (leaf_count, input) = pad_input(input, size_hint);
-        let level_count = get_level_count(leaf_count);
+        Self::from_iter_with_layout(input, size_hint, TreeLayout::Padded, store)
+    }
+
+    /// Constructs a Merkle tree from given input data, with the leaves laid out according to `layout`.
+    /// eg. `MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, store)`
+    pub fn construct_with_layout<B, I, U>(input: I, layout: TreeLayout, store: Store) -> Self
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+        I: IntoIterator<IntoIter = U>,
+        U: Iterator<Item = B>,
+    {
+        let input = input.into_iter();
+        let size_hint = input.size_hint().1.unwrap_or_default();
+        Self::from_iter_with_layout(input, size_hint, layout, store)
+    }
+    /// panics on empty input or a failing store, see `try_from_iter_with_layout`
+    pub fn from_iter_with_layout<B, I>(
+        input: I,
+        size_hint: usize,
+        layout: TreeLayout,
+        store: Store,
+    ) -> Self
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Sized,
+        I: Iterator<Item = B>,
+    {
+        Self::try_from_iter_with_layout(input, size_hint, layout, store)
+            .expect("failed to construct the tree")
+    }
+    pub fn try_construct_with_layout<B, I, U>(
+        input: I,
+        layout: TreeLayout,
+        store: Store,
+    ) -> Result<Self, MerkleError>
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+        I: IntoIterator<IntoIter = U>,
+        U: Iterator<Item = B>,
+    {
+        let input = input.into_iter();
+        let size_hint = input.size_hint().1.unwrap_or_default();
+        Self::try_from_iter_with_layout(input, size_hint, layout, store)
+    }
+    pub fn try_from_iter_with_layout<B, I>(
+        input: I,
+        size_hint: usize,
+        layout: TreeLayout,
+        store: Store,
+    ) -> Result<Self, MerkleError>
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone + Sized,
+        I: Iterator<Item = B>,
+    {
+        let leaves = input.map(|data| Node::new::<H>(data, true));
+        Self::try_from_leaves(leaves, size_hint, layout, store)
+    }
+    /// `try_from_iter_with_layout` for already hashed leaves
+    pub(crate) fn try_from_leaves<I: Iterator<Item = Node>>(
+        input: I,
+        size_hint: usize,
+        layout: TreeLayout,
+        store: Store,
+    ) -> Result<Self, MerkleError> {
+        if size_hint == 0 {
+            let mut tree = Self::empty(layout, store);
+            tree.save_metadata()?;
+            return Ok(tree);
+        }
         let mut tree_cache = store;
         let lowest_level = 0;
-        let (_root_path, root, count) =
-            build_tree(&mut tree_cache, input, level_count, lowest_level, false, 0);
-
-        let unique_leaf_count = count;
-        let padding_start = unique_leaf_count.saturating_sub(1);
+        let (is_padded, leaf_count, root, unique_leaf_count) = match layout {
+            TreeLayout::Padded => {
+                let (leaf_count, input) = pad_leaves(input, size_hint)?;
+                let (_root_path, root, count) = build_tree::<_, H>(
+                    &mut tree_cache,
+                    input,
+                    get_level_count(leaf_count),
+                    lowest_level,
+                    false,
+                    0,
+                )?;
+                (!size_hint.is_power_of_two(), leaf_count, root, count)
+            }
+            TreeLayout::Unbalanced => {
+                // no padding, every leaf is unique by position
+                let (_root_path, root, _) = build_tree::<_, H>(
+                    &mut tree_cache,
+                    input,
+                    get_level_count(size_hint),
+                    lowest_level,
+                    false,
+                    0,
+                )?;
+                (false, size_hint, root, size_hint)
+            }
+        };
+        let level_count = get_level_count(leaf_count);
+        // index of the last leaf before the padding
+        let padding_start = size_hint.saturating_sub(1);
 
         tree_cache.sort();
 
-        Self {
+        let mut tree = Self {
             lowest_level,
             padding_start,
             root: root.data,
@@ -115,78 +284,512 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
             leaf_count,
             level_count,
             tree_cache,
+            layout,
+            hasher: PhantomData,
+        };
+        tree.save_metadata()?;
+        Ok(tree)
+    }
+    /// panics on a failing store, see `try_construct_sorted`
+    pub fn construct_sorted<B, I>(input: I, store: Store) -> Self
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+        I: IntoIterator<Item = B>,
+    {
+        Self::try_construct_sorted(input, store).expect("failed to construct the tree")
+    }
+    /// Constructs a tree of the input sorted by leaf hash without duplicates, (eg. for revocation lists)
+    /// so `prove_absence` can show that data is not in it. The leaf hash is the key of the data,
+    /// sorting by it keeps the order verifiable from the proven leaves alone.
+    /// Sorted trees use the unbalanced layout, so no padding follows the last leaf.
+    /// Appending or updating leaves doesn't keep the order
+    pub fn try_construct_sorted<B, I>(input: I, store: Store) -> Result<Self, MerkleError>
+    where
+        B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
+        I: IntoIterator<Item = B>,
+    {
+        let mut leaves: Vec<_> = input
+            .into_iter()
+            .map(|data| (H::hash_leaf(&data), data))
+            .collect();
+        leaves.sort_unstable_by_key(|(hash, _)| *hash);
+        leaves.dedup_by_key(|(hash, _)| *hash);
+        let sorted = leaves.into_iter().map(|(_, data)| data);
+        Self::try_construct_with_layout(sorted, TreeLayout::Unbalanced, store)
+    }
+
+    /// Reopens a tree persisted in `store`, (eg. after a restart) from the metadata saved
+    /// whenever the tree changes, without rehashing any leaves
+    pub fn open_with_hasher(store: Store) -> Result<Self, MerkleError> {
+        let bytes = store.load_metadata()?.ok_or(MerkleError::MissingMetadata)?;
+        let metadata = TreeMetadata::from_bytes(&bytes)?;
+        if metadata.algorithm != H::ALGORITHM || metadata.domain_separated != H::DOMAIN_SEPARATED {
+            return Err(MerkleError::InvalidMetadata(format!(
+                "the tree was built with {}{}",
+                metadata.algorithm.name(),
+                if metadata.domain_separated {
+                    " (domain separated)"
+                } else {
+                    ""
+                }
+            )));
+        }
+        let mut tree_cache = store;
+        tree_cache.sort();
+        Ok(Self::with_metadata(metadata, tree_cache))
+    }
+    /// the tree described by `metadata` over the nodes in `store`
+    pub(crate) fn with_metadata(metadata: TreeMetadata, store: Store) -> Self {
+        let mut tree = Self::empty(metadata.layout, store);
+        tree.set_metadata(metadata);
+        tree
+    }
+    /// takes the state of a tree with the same nodes, (eg. after applying staged writes)
+    pub(crate) fn set_metadata(&mut self, metadata: TreeMetadata) {
+        self.root = metadata.root;
+        self.is_padded = metadata.is_padded;
+        self.leaf_count = metadata.leaf_count;
+        self.level_count = metadata.level_count;
+        self.lowest_level = metadata.lowest_level;
+        self.unique_leaf_count = metadata.unique_leaf_count;
+        self.padding_start = metadata.padding_start;
+        self.layout = metadata.layout;
+    }
+
+    /// the state needed to reopen this tree from its store
+    pub fn metadata(&self) -> TreeMetadata {
+        TreeMetadata {
+            root: self.root,
+            is_padded: self.is_padded,
+            leaf_count: self.leaf_count,
+            level_count: self.level_count,
+            lowest_level: self.lowest_level,
+            unique_leaf_count: self.unique_leaf_count,
+            padding_start: self.padding_start,
+            layout: self.layout,
+            algorithm: H::ALGORITHM,
+            domain_separated: H::DOMAIN_SEPARATED,
         }
     }
+
+    fn save_metadata(&mut self) -> Result<(), MerkleError> {
+        let metadata = self.metadata().to_bytes();
+        self.tree_cache.save_metadata(&metadata)
+    }
+    /// a tree without leaves, its root is `empty_root`
+    fn empty(layout: TreeLayout, store: Store) -> Self {
+        Self {
+            root: Self::empty_root(),
+            is_padded: false,
+            leaf_count: 0,
+            level_count: 0,
+            lowest_level: 0,
+            unique_leaf_count: 0,
+            padding_start: 0,
+            tree_cache: store,
+            layout,
+            hasher: PhantomData,
+        }
+    }
+    /// the root of a tree without leaves, the hash of no data (as in RFC 6962).
+    /// A tree with a single leaf has the leaf's hash, `H::hash_leaf(&data)` as its root
+    pub fn empty_root() -> Hash {
+        H::hash_data(&[])
+    }
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+    /// number of leaves in the tree, not counting the padding of padded trees
+    pub fn len(&self) -> usize {
+        match self.layout {
+            _ if self.is_empty() => 0,
+            TreeLayout::Padded => self.padding_start + 1,
+            TreeLayout::Unbalanced => self.leaf_count,
+        }
+    }
+    pub fn layout(&self) -> TreeLayout {
+        self.layout
+    }
+    /// level of the leaves, (the root is at the lowest level)
+    pub fn level_count(&self) -> isize {
+        self.level_count
+    }
+    pub fn lowest_level(&self) -> isize {
+        self.lowest_level
+    }
+    /// the path of the node at `index` on `level`, (the root is keyed with the Center direction)
+    pub fn path_at(&self, level: isize, index: usize) -> PathTrace {
+        if level == self.lowest_level {
+            return PathTrace::root(self.lowest_level);
+        }
+        PathTrace::new(HashDirection::from_index(index), level, index)
+    }
     /// update th target_hash with a new one.
+    /// both hashes are leaf hashes, ie. `H::hash_leaf(&data)`
     pub fn update(&mut self, target_hash: &Hash, new: Hash) {
-        if let Some(current) = self.fetch_cache_pathtrace(target_hash) {
-            if let Some(mut target_node) = self.tree_cache.get(&current) {
-                target_node.data = new;
-                self.tree_cache.update_value(&current, target_node);
-                self.cascade_update(current);
+        match self.try_update(target_hash, new) {
+            // updating a hash that isn't in the tree does nothing
+            Ok(()) | Err(MerkleError::HashNotFound(_)) => {}
+            Err(error) => panic!("failed to update the tree: {error}"),
+        }
+    }
+    /// update th target_hash with a new one, `MerkleError::HashNotFound` if no leaf holds it
+    pub fn try_update(&mut self, target_hash: &Hash, new: Hash) -> Result<(), MerkleError> {
+        let index = self
+            .find_leaf(target_hash)?
+            .ok_or(MerkleError::HashNotFound(*target_hash))?;
+        let leaf = Node {
+            data: new,
+            is_leaf: true,
+            from_duplicate: false,
+        };
+        self.update_leaves(BTreeMap::from([(index, leaf)]))
+    }
+
+    /// panics if an index is out of range or the store fails, see `try_update_many`
+    pub fn update_many<D, I>(&mut self, updates: I)
+    where
+        D: AsRef<[u8]> + Sync,
+        I: IntoIterator<Item = (usize, D)>,
+    {
+        self.try_update_many(updates)
+            .expect("failed to update the tree");
+    }
+    /// replaces the leaves at the given indexes with the hashes of the new data.
+    /// The ancestors shared by the updated leaves are rehashed once, a level at a time (in parallel)
+    pub fn try_update_many<D, I>(&mut self, updates: I) -> Result<(), MerkleError>
+    where
+        D: AsRef<[u8]> + Sync,
+        I: IntoIterator<Item = (usize, D)>,
+    {
+        let updates: Vec<(usize, D)> = updates.into_iter().collect();
+        if updates.is_empty() {
+            return Ok(());
+        }
+        if let Some((index, _)) = updates.iter().find(|(index, _)| *index >= self.len()) {
+            return Err(MerkleError::LeafIndexOutOfRange(*index));
+        }
+        let leaves: BTreeMap<usize, Node> = updates
+            .par_iter()
+            .map(|(index, data)| (*index, Node::new::<H>(data, true)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect();
+        self.update_leaves(leaves)
+    }
+    /// writes the given leaves with their padding and rehashes their ancestors, see `try_update_many`
+    fn update_leaves(&mut self, mut leaves: BTreeMap<usize, Node>) -> Result<(), MerkleError> {
+        let len = self.len();
+        // the padding copies the last leaf
+        if let Some(&last) = leaves.get(&(len - 1)) {
+            for position in len..self.leaf_count {
+                let padding = Node {
+                    from_duplicate: true,
+                    ..last
+                };
+                leaves.insert(position, padding);
             }
         }
+        let mut updated: BTreeMap<PathTrace, Node> = leaves
+            .iter()
+            .map(|(&index, &leaf)| (self.path_at(self.level_count, index), leaf))
+            .collect();
+        let dirty: BTreeSet<PathTrace> = updated
+            .keys()
+            .flat_map(|leaf| leaf.generate_route(self.lowest_level).skip(1))
+            .collect();
+        // deepest level first, so the children of every parent are already rehashed
+        for (level, parents) in &dirty.iter().rev().chunk_by(|path| path.level) {
+            let child = |index| self.path_at(level + 1, index);
+            let get = |path: PathTrace| match updated.get(&path) {
+                Some(node) => Ok(Some(*node)),
+                None => self.tree_cache.try_get(&path),
+            };
+            let mut pairs = vec![];
+            for parent in parents {
+                let left = get(child(2 * parent.index))?;
+                let right = get(child(2 * parent.index + 1))?;
+                pairs.push((*parent, left, right));
+            }
+            let parents: Vec<(PathTrace, Node)> = pairs
+                .into_par_iter()
+                .flat_map(|(parent, left, right)| {
+                    let left = left?;
+                    let node = match right {
+                        Some(right) => Node {
+                            data: H::hash_concat(&left.data, &right.data),
+                            is_leaf: false,
+                            from_duplicate: right.from_duplicate,
+                        },
+                        // promoted unchanged, (RFC 6962 layout)
+                        None => Node {
+                            is_leaf: false,
+                            ..left
+                        },
+                    };
+                    Some((parent, node))
+                })
+                .collect();
+            updated.extend(parents);
+        }
+        // leaves first, then each level up in index order, so the lookup of a hash shared with
+        // a padding or promoted copy points at the leaf, (stores keep the first lookup they see)
+        let writes = updated
+            .iter()
+            .sorted_by_key(|(path, _)| (std::cmp::Reverse(path.level), path.index));
+        for (path, node) in writes {
+            if path.level == self.level_count {
+                self.overwrite_leaf(path.index, *node)?;
+            } else {
+                self.tree_cache.try_set(*path, *node)?;
+            }
+        }
+        self.tree_cache.try_trigger_batch_actions()?;
+        if let Some(root) = updated.get(&PathTrace::root(self.lowest_level)) {
+            self.root = root.data;
+        }
+        self.tree_cache.sort();
+        self.save_metadata()
     }
 
-    /// updates the hash up every level to the root.
+    /// panics if the store fails, see `try_update_leaf`
+    pub fn update_leaf<D: AsRef<[u8]> + Sync>(&mut self, index: usize, data: &D) -> Option<Hash> {
+        match self.try_update_leaf(index, data) {
+            Ok(old) => Some(old),
+            Err(MerkleError::LeafIndexOutOfRange(_)) => None,
+            Err(error) => panic!("failed to update the tree: {error}"),
+        }
+    }
+    /// replaces the leaf at `index` with the hash of `data`, returning the old leaf hash.
+    /// `MerkleError::LeafIndexOutOfRange` if there is no leaf at `index`
+    pub fn try_update_leaf<D: AsRef<[u8]> + Sync>(
+        &mut self,
+        index: usize,
+        data: &D,
+    ) -> Result<Hash, MerkleError> {
+        let old = self
+            .tree_cache
+            .try_get(&self.path_at(self.level_count, index))?
+            .filter(|node| node.is_leaf && index < self.len())
+            .ok_or(MerkleError::LeafIndexOutOfRange(index))?;
+        self.try_update_many([(index, data)])?;
+        Ok(old.data)
+    }
+    /// panics if the store fails, see `try_replace`
+    pub fn replace<D: AsRef<[u8]> + Sync>(&mut self, old_data: &D, new_data: &D) -> Option<Hash> {
+        match self.try_replace(old_data, new_data) {
+            Ok(old) => Some(old),
+            Err(MerkleError::HashNotFound(_)) => None,
+            Err(error) => panic!("failed to update the tree: {error}"),
+        }
+    }
+    /// replaces the leaf holding `old_data` with `new_data`, returning the old leaf hash.
+    /// Unlike `update` only leaves are matched, `MerkleError::HashNotFound` if no leaf holds `old_data`
+    pub fn try_replace<D: AsRef<[u8]> + Sync>(
+        &mut self,
+        old_data: &D,
+        new_data: &D,
+    ) -> Result<Hash, MerkleError> {
+        let old = H::hash_leaf(old_data);
+        let index = self
+            .find_leaf(&old)?
+            .ok_or(MerkleError::HashNotFound(old))?;
+        self.try_update_many([(index, new_data)])?;
+        Ok(old)
+    }
+    /// the index of a leaf with the given hash, ignoring interior nodes with the same hash.
+    /// When the lookup index doesn't lead to a leaf the leaves are scanned,
+    /// so data that isn't in the tree costs a read of every leaf
+    fn find_leaf(&self, hash: &Hash) -> Result<Option<usize>, MerkleError> {
+        let len = self.len();
+        let is_leaf_with_hash =
+            |node: Option<Node>| node.is_some_and(|node| node.is_leaf && node.data == *hash);
+        // the lookup index of a store can still point at a node that was removed or updated since
+        if let Some(path) = self.tree_cache.try_get_key_by_hash(hash)? {
+            if path.level == self.level_count
+                && path.index < len
+                && is_leaf_with_hash(self.tree_cache.try_get(&path)?)
+            {
+                return Ok(Some(path.index));
+            }
+        }
+        // or at a padded or promoted copy of the last leaf, or have gone with an overwritten
+        // copy of the same data
+        for index in (0..len).rev() {
+            let leaf = self.path_at(self.level_count, index);
+            if is_leaf_with_hash(self.tree_cache.try_get(&leaf)?) {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    /// updates the hash up every level to the root, panics if the store fails
+    #[deprecated(note = "use `try_update_leaf`, which also rehashes the ancestors of the leaf")]
     pub fn cascade_update(&mut self, current: PathTrace) {
-        current.generate_route(self.lowest_level).for_each(|path| {
-            if let (Some(current_node), Some(sibling_node)) = (
-                self.tree_cache.get(&path),
-                self.tree_cache.get(&path.get_sibling_path()),
-            ) {
-                // check for direction
-                let next_parent_hash = if path.direction == HashDirection::Left {
-                    GlobalHasher::hash_concat(&current_node.data, &sibling_node.data)
-                } else {
-                    GlobalHasher::hash_concat(&sibling_node.data, &current_node.data)
+        self.try_cascade_update(current)
+            .and_then(|()| self.save_metadata())
+            .expect("failed to update the tree");
+    }
+    /// updates the hash up every level to the root.
+    /// A node without a sibling is promoted unchanged to its parent, (RFC 6962 layout)
+    pub(crate) fn try_cascade_update(&mut self, current: PathTrace) -> Result<(), MerkleError> {
+        for path in current.generate_route(self.lowest_level) {
+            let (Some(current_node), Some(parent_path)) = (
+                self.tree_cache.try_get(&path)?,
+                path.get_parent_path(self.lowest_level),
+            ) else {
+                continue;
+            };
+            // check for direction
+            let next_parent_hash = match self.tree_cache.try_get(&path.get_sibling_path())? {
+                Some(sibling_node) if path.direction == HashDirection::Left => {
+                    H::hash_concat(&current_node.data, &sibling_node.data)
+                }
+                Some(sibling_node) => H::hash_concat(&sibling_node.data, &current_node.data),
+                None => current_node.data,
+            };
+            if let Some(mut parent_node) = self.tree_cache.try_get(&parent_path)? {
+                parent_node.data = next_parent_hash;
+                self.tree_cache
+                    .try_update_value(&parent_path, parent_node)?;
+            } else {
+                let parent_node = Node {
+                    data: next_parent_hash,
+                    is_leaf: false,
+                    from_duplicate: false,
                 };
-                path.get_parent_path(self.lowest_level)
-                    .and_then(|parent_path| {
-                        if let Some(mut parent_node) = self.tree_cache.get(&parent_path) {
-                            parent_node.data = next_parent_hash;
-                            self.tree_cache.update_value(&parent_path, parent_node);
-                        }
-                        None as Option<Node>
-                    });
-                //parent_node.data = next_parent_hash;
+                self.tree_cache.try_set(parent_path, parent_node)?;
+                self.tree_cache.try_trigger_batch_actions()?;
             }
-        });
+        }
         // update self. root;
-        if let Some(new_root) = self.tree_cache.get(&PathTrace::root(self.lowest_level)) {
+        if let Some(new_root) = self
+            .tree_cache
+            .try_get(&PathTrace::root(self.lowest_level))?
+        {
             self.root = new_root.data;
         }
+        Ok(())
     }
 
+    /// panics if the store fails, see `try_append`
     pub fn append<D: AsRef<[u8]>>(&mut self, data: &D) {
-        if !self.is_padded {
-            self.expand_tree(data);
-            self.tree_cache.sort();
-            return;
+        self.try_append(data).expect("failed to append to the tree");
+    }
+    pub fn try_append<D: AsRef<[u8]>>(&mut self, data: &D) -> Result<(), MerkleError> {
+        if self.is_empty() {
+            self.insert_first(Node::new::<H>(data, true))?;
+        } else if self.layout == TreeLayout::Unbalanced {
+            self.expand_unbalanced(data)?;
+        } else if !self.is_padded {
+            self.try_expand_tree(data)?;
+        } else {
+            // handling cases of adding to un already unbalanced tree with padding;
+            self.try_expand_padded(data)?;
         }
-        // handling cases of adding to un already unbalanced tree with padding;
-        self.expand_padded(data);
+        self.appended()
+    }
+    /// sorts the store and persists the metadata once a leaf was added
+    fn appended(&mut self) -> Result<(), MerkleError> {
         self.tree_cache.sort();
+        self.save_metadata()
     }
-    /// expands the tree by the next_power_of_two
+    /// the first leaf of an empty tree is also its root
+    fn insert_first(&mut self, leaf: Node) -> Result<(), MerkleError> {
+        self.tree_cache
+            .try_set(PathTrace::root(self.lowest_level), leaf)?;
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.root = leaf.data;
+        self.leaf_count = 1;
+        self.unique_leaf_count = 1;
+        Ok(())
+    }
+    /// panics if the store fails, see `try_append_batch`
+    pub fn append_batch<D, I>(&mut self, input: I)
+    where
+        D: AsRef<[u8]> + Sync,
+        I: IntoIterator<Item = D>,
+    {
+        self.try_append_batch(input)
+            .expect("failed to append to the tree");
+    }
+    /// appends all of `input` at once, the tree grows to the final size a single time,
+    /// the new leaves are hashed in parallel and every changed node is rehashed once
+    pub fn try_append_batch<D, I>(&mut self, input: I) -> Result<(), MerkleError>
+    where
+        D: AsRef<[u8]> + Sync,
+        I: IntoIterator<Item = D>,
+    {
+        let input: Vec<D> = input.into_iter().collect();
+        let mut leaves: Vec<Node> = input
+            .par_iter()
+            .map(|data| Node::new::<H>(data, true))
+            .collect();
+        let Some(&last) = leaves.last() else {
+            return Ok(());
+        };
+        let mut len = self.len();
+        if self.is_empty() {
+            self.insert_first(leaves.remove(0))?;
+            len = 1;
+        }
+        let new_len = len + leaves.len();
+        let width = match self.layout {
+            TreeLayout::Padded => new_len.next_power_of_two(),
+            TreeLayout::Unbalanced => new_len,
+        };
+        // grow to the final size, the current root becomes the left most node of its level
+        while (1_usize << (self.level_count - self.lowest_level)) < width {
+            self.shift_root_to_left()?;
+            self.lowest_level -= 1;
+        }
+        for (position, leaf) in (len..).zip(leaves) {
+            self.overwrite_leaf(position, leaf)?;
+        }
+        let padding = Node {
+            from_duplicate: true,
+            ..last
+        };
+        for position in new_len..width {
+            self.overwrite_leaf(position, padding)?;
+        }
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.leaf_count = width;
+        if len < width {
+            self.rehash_leaves(len, width - 1)?;
+        }
+        self.unique_leaf_count += new_len - len;
+        self.padding_start = new_len - 1;
+        self.is_padded = new_len < width;
+        self.tree_cache.sort();
+        self.save_metadata()
+    }
+    /// expands the tree by the next_power_of_two, panics if the store fails
+    #[deprecated(note = "use `try_append`, which expands the tree as its layout needs")]
     pub fn expand_tree<D: AsRef<[u8]>>(&mut self, data: &D) {
+        self.try_expand_tree(data)
+            .and_then(|()| self.appended())
+            .expect("failed to append to the tree");
+    }
+    /// expands the tree by the next_power_of_two
+    pub(crate) fn try_expand_tree<D: AsRef<[u8]>>(&mut self, data: &D) -> Result<(), MerkleError> {
         // leaf_counts is already a is_power_of_two
         let next_needed_nodes = (self.leaf_count + 1).next_power_of_two() - self.leaf_count;
-        let node = Node::new(data, true);
+        let node = Node::new::<H>(data, true);
         let input = std::iter::repeat_n(node, next_needed_nodes);
         let total_tree_nodes = 2 * next_needed_nodes - 1;
-        // shift_root_to_left
-        self.tree_cache.shift_root_to_left(self.lowest_level);
+        self.shift_root_to_left()?;
         self.tree_cache.reserve(total_tree_nodes);
-        let (_last, last_node, _) = build_tree(
+        let (_last, last_node, _) = build_tree::<_, H>(
             &mut self.tree_cache,
             input,
             self.level_count,
             self.lowest_level,
             true,
             self.leaf_count,
-        );
-        let next_root = GlobalHasher::hash_concat(self.root(), &last_node.data);
+        )?;
+        let next_root = H::hash_concat(self.root(), &last_node.data);
         self.root = next_root;
         self.leaf_count += next_needed_nodes;
         let root = Node {
@@ -196,74 +799,266 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
         };
         self.lowest_level -= 1;
         self.tree_cache
-            .set(PathTrace::root(self.lowest_level), root);
-        self.tree_cache.trigger_batch_actions();
+            .try_set(PathTrace::root(self.lowest_level), root)?;
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.unique_leaf_count += 1;
+        // the new leaf is the first of the new half, the rest of it is padding
+        self.padding_start = self.leaf_count - next_needed_nodes;
+        self.is_padded = next_needed_nodes > 1;
+        Ok(())
+    }
+    /// adds a single leaf to an RFC 6962 layout tree, without any padding
+    pub(crate) fn expand_unbalanced<D: AsRef<[u8]>>(
+        &mut self,
+        data: &D,
+    ) -> Result<(), MerkleError> {
+        let index = self.leaf_count;
+        let capacity = 1_usize << (self.level_count - self.lowest_level);
+        if index == capacity {
+            // the tree is full, the current root becomes the left child of a new root
+            self.shift_root_to_left()?;
+            self.lowest_level -= 1;
+        }
+        let leaf = PathTrace::new(HashDirection::from_index(index), self.level_count, index);
+        self.tree_cache.try_set(leaf, Node::new::<H>(data, true))?;
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.try_cascade_update(leaf)?;
+        self.leaf_count += 1;
         self.unique_leaf_count += 1;
         self.padding_start = self.unique_leaf_count - 1;
-        self.is_padded = !self.unique_leaf_count.is_power_of_two();
+        Ok(())
     }
+    /// replaces the first padded copy with the new leaf, panics if the store fails
+    #[deprecated(note = "use `try_append`, which expands the tree as its layout needs")]
     pub fn expand_padded<D: AsRef<[u8]>>(&mut self, data: &D) {
-        let padding_start = self.padding_start;
-        // replace the first padded copy with unique pair;
-        let hashed_data = GlobalHasher::hash_data(&data);
-        let mut first_padded = PathTrace::new(
-            HashDirection::from_index(padding_start),
-            self.level_count,
-            padding_start,
-        );
-        if !self.tree_cache.exists(&first_padded) {
-            first_padded.index += 1;
+        self.try_expand_padded(data)
+            .and_then(|()| self.appended())
+            .expect("failed to append to the tree");
+    }
+    /// replaces the first padded copy with the new leaf, which then also pads the rest
+    pub(crate) fn try_expand_padded<D: AsRef<[u8]>>(
+        &mut self,
+        data: &D,
+    ) -> Result<(), MerkleError> {
+        let index = self.padding_start + 1;
+        let hashed_data = H::hash_leaf(&data);
+        for position in index..self.leaf_count {
+            let leaf = Node {
+                data: hashed_data,
+                is_leaf: true,
+                from_duplicate: position != index,
+            };
+            self.tree_cache
+                .try_set(self.path_at(self.level_count, position), leaf)?;
+        }
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.rehash_leaves(index, self.leaf_count - 1)?;
+        self.unique_leaf_count += 1;
+        self.padding_start = index;
+        self.is_padded = index + 1 < self.leaf_count;
+        Ok(())
+    }
+    /// rehashes the parents of the leaves `first..=last`, a level at a time up to the root.
+    /// A node without a sibling is promoted unchanged, (RFC 6962 layout)
+    fn rehash_leaves(&mut self, first: usize, last: usize) -> Result<(), MerkleError> {
+        let (mut first, mut last) = (first, last);
+        for level in (self.lowest_level..self.level_count).rev() {
+            first /= 2;
+            last /= 2;
+            for parent_index in first..=last {
+                let child = |index| self.path_at(level + 1, index);
+                let Some(left) = self.tree_cache.try_get(&child(2 * parent_index))? else {
+                    continue;
+                };
+                let parent = match self.tree_cache.try_get(&child(2 * parent_index + 1))? {
+                    Some(right) => Node {
+                        data: H::hash_concat(&left.data, &right.data),
+                        is_leaf: false,
+                        from_duplicate: right.from_duplicate,
+                    },
+                    None => Node {
+                        is_leaf: false,
+                        ..left
+                    },
+                };
+                self.tree_cache
+                    .try_set(self.path_at(level, parent_index), parent)?;
+            }
+            self.tree_cache.try_trigger_batch_actions()?;
+        }
+        if let Some(root) = self
+            .tree_cache
+            .try_get(&PathTrace::root(self.lowest_level))?
+        {
+            self.root = root.data;
         }
-        let sibling_path = first_padded.get_sibling_path();
-        // if both the current and sibling are the same; and previous contains current,
-        // this is a complete dupliate pair, we need to replace both;
-        let mut previous = first_padded;
-        previous.index -= 2;
+        Ok(())
+    }
 
-        let any_previous_contain_current_hash = [previous, previous.get_sibling_path()]
-            .iter()
-            .any(|path| self.compare_hashes(&first_padded, path));
-        if self.compare_hashes(&first_padded, &sibling_path) {
-            if let Some(mut first_padded_node) = self.tree_cache.get(&first_padded) {
-                if any_previous_contain_current_hash {
-                    first_padded_node.data = hashed_data;
-                    first_padded_node.from_duplicate = false;
+    /// panics if the store fails, see `try_truncate`
+    pub fn truncate(&mut self, new_len: usize) {
+        self.try_truncate(new_len)
+            .expect("failed to truncate the tree");
+    }
+    /// keeps the first `new_len` leaves and drops the rest, (eg. to roll back appends).
+    /// Does nothing if the tree has `new_len` leaves or less
+    pub fn try_truncate(&mut self, new_len: usize) -> Result<(), MerkleError> {
+        if new_len >= self.len() {
+            return Ok(());
+        }
+        self.drop_leaves(new_len)?;
+        self.tree_cache.sort();
+        self.save_metadata()
+    }
+    /// panics if there is no leaf at `index` or the store fails, see `try_remove`
+    pub fn remove(&mut self, index: usize, mode: RemovalMode) {
+        self.try_remove(index, mode)
+            .expect("failed to remove the leaf");
+    }
+    /// removes the leaf at `index`, `MerkleError::LeafIndexOutOfRange` if there isn't one
+    pub fn try_remove(&mut self, index: usize, mode: RemovalMode) -> Result<(), MerkleError> {
+        let len = self.len();
+        if index >= len {
+            return Err(MerkleError::LeafIndexOutOfRange(index));
+        }
+        match mode {
+            RemovalMode::Tombstone => {
+                let tombstone = Node {
+                    data: TOMBSTONE,
+                    is_leaf: true,
+                    from_duplicate: false,
+                };
+                self.overwrite_leaf(index, tombstone)?;
+                if index + 1 == len && index + 1 < self.leaf_count {
+                    // the padding copies the last leaf
+                    for position in len..self.leaf_count {
+                        let padding = Node {
+                            from_duplicate: true,
+                            ..tombstone
+                        };
+                        self.overwrite_leaf(position, padding)?;
+                    }
+                    self.tree_cache.try_trigger_batch_actions()?;
+                    self.rehash_leaves(index, self.leaf_count - 1)?;
+                } else {
+                    self.tree_cache.try_trigger_batch_actions()?;
+                    self.try_cascade_update(self.path_at(self.level_count, index))?;
+                }
+            }
+            RemovalMode::Compact => {
+                let leaves = (index + 1..len)
+                    .map(|position| {
+                        self.tree_cache
+                            .try_get(&self.path_at(self.level_count, position))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // remove before setting, so the lookup of the shifted hashes isn't dropped
+                for position in index..len {
                     self.tree_cache
-                        .update_value(&first_padded, first_padded_node);
-                    self.tree_cache.trigger_batch_actions();
+                        .try_remove_node(self.path_at(self.level_count, position))?;
+                }
+                for (position, leaf) in (index..).zip(leaves) {
+                    if let Some(leaf) = leaf {
+                        self.tree_cache
+                            .try_set(self.path_at(self.level_count, position), leaf)?;
+                    }
+                }
+                self.tree_cache.try_trigger_batch_actions()?;
+                self.drop_leaves(len - 1)?;
+                if index + 1 < len {
+                    self.rehash_leaves(index, len - 2)?;
                 }
             }
         }
-        // replace the sibling of the first padding_hahsh;
-        if let Some(mut sibling) = self.tree_cache.get(&sibling_path) {
-            sibling.data = hashed_data;
-            self.tree_cache.update_value(&sibling_path, sibling);
-            self.tree_cache.trigger_batch_actions();
-            self.cascade_update(first_padded);
-        }
-
-        for next_index in (sibling_path.index..self.leaf_count).step_by(2).skip(1) {
-            let next_node = PathTrace::new(HashDirection::Right, sibling_path.level, next_index);
-            if let Some(mut found_right) = self.tree_cache.get(&next_node) {
-                found_right.data = hashed_data;
-                self.tree_cache.update_value(&next_node, found_right);
-                let left = Node {
-                    data: hashed_data,
-                    is_leaf: true,
+        self.tree_cache.sort();
+        self.save_metadata()
+    }
+    /// change the direction of the current root from (level, Center, 0) to (level, left, 0)
+    fn shift_root_to_left(&mut self) -> Result<(), MerkleError> {
+        stores::try_shift_root_to_left(&mut self.tree_cache, self.lowest_level)
+    }
+    /// replaces the leaf at `position`, dropping the lookup of the old hash
+    /// so the removed data can't be proven and can be added back later
+    fn overwrite_leaf(&mut self, position: usize, leaf: Node) -> Result<(), MerkleError> {
+        let path = self.path_at(self.level_count, position);
+        if self
+            .tree_cache
+            .try_get(&path)?
+            .is_some_and(|old| old.data != leaf.data)
+        {
+            self.tree_cache.try_remove_node(path)?;
+        }
+        self.tree_cache.try_set(path, leaf)?;
+        Ok(())
+    }
+    /// drops the leaves from `new_len` on with every node above only them,
+    /// and the roots left without a right subtree, then rehashes the new last leaf
+    fn drop_leaves(&mut self, new_len: usize) -> Result<(), MerkleError> {
+        let width = match self.layout {
+            _ if new_len == 0 => 0,
+            TreeLayout::Padded => new_len.next_power_of_two(),
+            TreeLayout::Unbalanced => new_len,
+        };
+        for level in self.lowest_level..=self.level_count {
+            let span = 1_usize << (self.level_count - level);
+            for index in width.div_ceil(span)..self.leaf_count.div_ceil(span) {
+                self.tree_cache
+                    .try_remove_node(self.path_at(level, index))?;
+            }
+        }
+        if new_len == 0 {
+            self.root = Self::empty_root();
+            self.leaf_count = 0;
+            self.level_count = 0;
+            self.lowest_level = 0;
+            self.unique_leaf_count = 0;
+            self.padding_start = 0;
+            self.is_padded = false;
+            return Ok(());
+        }
+        // the tree halves, the left child of the root becomes the root
+        while self.level_count - self.lowest_level > get_level_count(width) {
+            self.tree_cache
+                .try_remove_node(PathTrace::root(self.lowest_level))?;
+            self.lowest_level += 1;
+            let left = PathTrace::new(HashDirection::Left, self.lowest_level, 0);
+            if let Some(node) = self.tree_cache.try_get(&left)? {
+                self.tree_cache.try_remove_node(left)?;
+                self.tree_cache
+                    .try_set(PathTrace::root(self.lowest_level), node)?;
+            }
+            // removals aren't batched, so apply the new root before the next one
+            self.tree_cache.try_trigger_batch_actions()?;
+        }
+        self.leaf_count = width;
+        let last = self.path_at(self.level_count, new_len - 1);
+        match self.layout {
+            TreeLayout::Padded if new_len < width => {
+                let last = self.tree_cache.try_get(&last)?.unwrap_or_default();
+                let padding = Node {
                     from_duplicate: true,
+                    ..last
                 };
-                self.tree_cache.set(next_node.get_sibling_path(), left);
-                self.cascade_update(next_node);
+                for position in new_len..width {
+                    self.overwrite_leaf(position, padding)?;
+                }
+                self.tree_cache.try_trigger_batch_actions()?;
+                self.rehash_leaves(new_len, width - 1)?;
             }
+            TreeLayout::Padded => {
+                if let Some(root) = self
+                    .tree_cache
+                    .try_get(&PathTrace::root(self.lowest_level))?
+                {
+                    self.root = root.data;
+                }
+            }
+            TreeLayout::Unbalanced => self.try_cascade_update(last)?,
         }
-        self.tree_cache.trigger_batch_actions();
-        self.unique_leaf_count += 1;
-        self.padding_start = if self.is_padded {
-            std::cmp::min(self.padding_start + 2, self.unique_leaf_count)
-        } else {
-            self.unique_leaf_count - 1
-        };
-        self.is_padded = !self.unique_leaf_count.is_power_of_two();
+        self.unique_leaf_count = new_len;
+        self.padding_start = new_len - 1;
+        self.is_padded = new_len < width;
+        Ok(())
     }
     pub fn compare_hashes(&self, left: &PathTrace, right: &PathTrace) -> bool {
         self.tree_cache.get(left).map(|node| node.data)
@@ -303,19 +1098,19 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
 
     pub fn find_data_route<D: AsRef<[u8]>>(&self, data: &D) -> Vec<PathTrace> {
         // faster path, fetch the path from leaf_set;
-        let target_hash = GlobalHasher::hash_data(data);
+        let target_hash = H::hash_leaf(data);
         if let Some(trace) = self.tree_cache.get_key_by_hash(&target_hash) {
             return trace.generate_route(self.lowest_level).collect();
         }
-        let target_hash = GlobalHasher::hash_data(data);
+        let target_hash = H::hash_leaf(data);
         if let Some(cached_path) = self.fetch_cache_pathtrace(&target_hash) {
             return cached_path.generate_route(self.lowest_level).collect();
         }
         vec![]
     }
 
-    /// Verifies that the given input data produces the given root hash
-    pub fn verify<D: AsRef<[u8]> + Eq + Clone + std::hash::Hash, I, U, B>(
+    /// Verifies that the given input data produces the given root hash, using `H`
+    pub fn verify_with_hasher<D: AsRef<[u8]> + Eq + Clone + std::hash::Hash, I, U, B>(
         input: I,
         root_hash: &D,
         store: Store,
@@ -326,13 +1121,14 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
         B: AsRef<[u8]> + std::hash::Hash + Eq + Clone,
     {
         let root_hash = root_hash.as_ref();
-        let generated_tree = Self::construct(input, store);
+        let generated_tree = Self::construct_with_hasher(input, store);
         generated_tree.root() == root_hash
     }
 
     /// Verifies that the given data and proof_path correctly produce the given root_hash
+    /// the directions in the proof are trusted, use `verify_proof_at` to also check the position
     pub fn verify_proof<D: AsRef<[u8]>>(data: &D, proof: &Proof, root_hash: &Hash) -> bool {
-        let hashed_data = GlobalHasher::hash_data(data);
+        let hashed_data = H::hash_leaf(data);
         let generated =
             proof
                 .hashes
@@ -340,33 +1136,135 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
                 .fold(hashed_data, |acc, &(_, direction, ref next_hash)| {
                     let is_leaf = direction == HashDirection::Left;
                     if is_leaf {
-                        return GlobalHasher::hash_concat(next_hash, &acc);
+                        return H::hash_concat(next_hash, &acc);
                     }
-                    GlobalHasher::hash_concat(&acc, next_hash)
+                    H::hash_concat(&acc, next_hash)
                 });
         &generated == root_hash
     }
 
     /// Returns a list of hashes that can be used to prove that the given data is in this tree
     pub fn prove<D: AsRef<[u8]>>(&self, data: &D) -> Option<Proof> {
+        self.try_prove(data).ok().flatten()
+    }
+    /// `prove`, reporting the errors of the store
+    pub(crate) fn try_prove<D: AsRef<[u8]>>(&self, data: &D) -> Result<Option<Proof>, MerkleError> {
         // we use our tree_cache and some math to calculate the sibling_node at each parent level
         // See PathTrace for math
-        let target_hash = GlobalHasher::hash_data(&data);
-        if let Some(trace) = self.fetch_cache_pathtrace(&target_hash) {
-            let hashes: Vec<_> = trace
-                .generate_route(self.lowest_level)
-                .take_while(|path| path.level >= (self.lowest_level + 1)) // ingnore the root
-                .flat_map(|mut path| {
-                    path = path.get_sibling_path();
-                    self.tree_cache
-                        .get(&path)
-                        .map(|node| (path.level, path.direction, node.data))
-                })
-                .collect();
+        match self.find_leaf(&H::hash_leaf(&data))? {
+            Some(leaf_index) => self.try_prove_index(leaf_index),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the proof for the leaf at `leaf_index`, which also works when the same data
+    /// is stored at several positions. `None` from `len()` on, the padding isn't proven on its own
+    pub fn prove_index(&self, leaf_index: usize) -> Option<Proof> {
+        self.try_prove_index(leaf_index).ok().flatten()
+    }
+    /// `prove_index`, reporting the errors of the store
+    pub(crate) fn try_prove_index(&self, leaf_index: usize) -> Result<Option<Proof>, MerkleError> {
+        if leaf_index >= self.len() {
+            return Ok(None);
+        }
+        let leaf = self.path_at(self.level_count, leaf_index);
+        let mut hashes = vec![];
+        for path in leaf
+            .generate_route(self.lowest_level)
+            .take_while(|path| path.level >= (self.lowest_level + 1))
+        // ingnore the root
+        {
+            let path = path.get_sibling_path();
+            // a missing sibling means the node was promoted, (unbalanced layout)
+            if let Some(node) = self.tree_cache.try_get(&path)? {
+                hashes.push((path.level, path.direction, node.data));
+            }
+        }
+
+        Ok(Some(Proof {
+            hashes,
+            leaf_index,
+            leaf_count: self.leaf_count,
+            root: self.root,
+            algorithm: H::ALGORITHM,
+            domain_separated: H::DOMAIN_SEPARATED,
+        }))
+    }
 
-            return Some(Proof { hashes });
+    /// Verifies that the given data is the leaf at `leaf_index` of a tree with `leaf_count` leaves
+    /// and the given root hash, `leaf_count` includes the padding for padded trees.
+    /// The directions in the proof have to match the ones computed from the position,
+    /// and the proof's root and hash algorithm the ones of this verifier
+    pub fn verify_proof_at<D: AsRef<[u8]>>(
+        leaf_index: usize,
+        leaf_count: usize,
+        data: &D,
+        proof: &Proof,
+        root_hash: &Hash,
+    ) -> bool {
+        proof.leaf_index == leaf_index
+            && Self::verify_leaf_proof(leaf_count, &H::hash_leaf(data), proof, root_hash)
+    }
+    /// `verify_proof_at` for an already hashed leaf, at the position in the proof.
+    /// `leaf_count` has to be trusted, it fixes the length of the path, so an interior node
+    /// can't be proven as a leaf of a smaller tree
+    fn verify_leaf_proof(leaf_count: usize, leaf: &Hash, proof: &Proof, root_hash: &Hash) -> bool {
+        proof.leaf_count == leaf_count
+            && &proof.root == root_hash
+            && proof.algorithm == H::ALGORITHM
+            && proof.domain_separated == H::DOMAIN_SEPARATED
+            && proof
+                .hashes
+                .iter()
+                .map(|(_, direction, _)| *direction)
+                .eq(sibling_directions(proof.leaf_index, proof.leaf_count))
+            && Self::verify_leaf_hashes_at(
+                proof.leaf_index,
+                proof.leaf_count,
+                leaf,
+                &proof.sibling_hashes(),
+                root_hash,
+            )
+    }
+
+    /// Verifies a proof given as only the sibling hashes, (leaf first) with the directions
+    /// computed from the leaf position and the tree size
+    pub fn verify_hashes_at<D: AsRef<[u8]>>(
+        leaf_index: usize,
+        leaf_count: usize,
+        data: &D,
+        hashes: &[Hash],
+        root_hash: &Hash,
+    ) -> bool {
+        Self::verify_leaf_hashes_at(
+            leaf_index,
+            leaf_count,
+            &H::hash_leaf(data),
+            hashes,
+            root_hash,
+        )
+    }
+    fn verify_leaf_hashes_at(
+        leaf_index: usize,
+        leaf_count: usize,
+        leaf: &Hash,
+        hashes: &[Hash],
+        root_hash: &Hash,
+    ) -> bool {
+        if leaf_index >= leaf_count {
+            return false;
+        }
+        let mut directions = sibling_directions(leaf_index, leaf_count);
+        let mut generated = *leaf;
+        for next_hash in hashes {
+            generated = match directions.next() {
+                Some(HashDirection::Left) => H::hash_concat(next_hash, &generated),
+                Some(_) => H::hash_concat(&generated, next_hash),
+                // more hashes than levels
+                None => return false,
+            };
         }
-        None
+        directions.next().is_none() && &generated == root_hash
     }
 
     pub fn pretty_print(&self) {
@@ -394,7 +1292,7 @@ impl<Store: NodeStore + Send> MerkleTree<Store> {
                     )
                 };
                 println!("{}", header);
-                nodes.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                nodes.sort_unstable_by_key(|a| a.0);
                 nodes.into_iter().for_each(|(path, node)| {
                     let is_leaf = path.direction == HashDirection::Left;
                     let prefix = "    ";
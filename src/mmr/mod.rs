@@ -0,0 +1,161 @@
+mod proof;
+use crate::hashers::{GlobalHasher, HashAlgorithm, Hasher};
+use crate::{Hash, HashDirection, MerkleError, Node, NodeStore, PathTrace};
+pub use proof::*;
+use std::marker::PhantomData;
+
+/// An append-only Merkle Mountain Range, a list of perfect trees, (mountains) one for every
+/// set bit of the leaf count, so appends never pad or rebuild and take O(log n) hashes.
+/// The root bags the peaks of the mountains from the right.
+/// Nodes are keyed by `PathTrace` with `level` as the height above the leaves,
+/// and `index` as the position of the node among the nodes of that height
+#[derive(Debug)]
+pub struct MerkleMountainRange<Store: NodeStore, H: Hasher = GlobalHasher> {
+    root: Hash,
+    leaf_count: usize,
+    pub tree_cache: Store,
+    hasher: PhantomData<H>,
+}
+
+/// the mountains of a range with `leaf_count` leaves from the left,
+/// as (height, index of the peak among the nodes of its height)
+pub fn mountains(leaf_count: usize) -> impl Iterator<Item = (usize, usize)> {
+    let mut start = 0;
+    (0..usize::BITS as usize)
+        .rev()
+        .filter(move |height| leaf_count & (1 << height) != 0)
+        .map(move |height| {
+            let mountain = (height, start >> height);
+            start += 1 << height;
+            mountain
+        })
+}
+/// the key of the node at `index` among the nodes `height` above the leaves
+pub fn mmr_path(height: usize, index: usize) -> PathTrace {
+    PathTrace::new(HashDirection::from_index(index), height as isize, index)
+}
+
+// constructors using the default hasher, (type parameter defaults are not used for inference)
+impl<Store: NodeStore> MerkleMountainRange<Store> {
+    /// an empty range
+    pub fn new(store: Store) -> Result<Self, MerkleError> {
+        Self::with_hasher(store)
+    }
+    /// Reopens a range persisted in `store`, see `open_with_hasher`
+    pub fn open(store: Store) -> Result<Self, MerkleError> {
+        Self::open_with_hasher(store)
+    }
+}
+
+impl<Store: NodeStore, H: Hasher> MerkleMountainRange<Store, H> {
+    const METADATA_VERSION: u8 = 1;
+
+    /// an empty range hashed with `H`
+    pub fn with_hasher(store: Store) -> Result<Self, MerkleError> {
+        let mut range = Self {
+            root: Self::bag_peaks(&[]),
+            leaf_count: 0,
+            tree_cache: store,
+            hasher: PhantomData,
+        };
+        range.save_metadata()?;
+        Ok(range)
+    }
+    /// Reopens a range persisted in `store`, (eg. after a restart) from the leaf count saved on every append
+    pub fn open_with_hasher(store: Store) -> Result<Self, MerkleError> {
+        let invalid = |reason: &str| MerkleError::InvalidMetadata(reason.to_string());
+        let bytes = store.load_metadata()?.ok_or(MerkleError::MissingMetadata)?;
+        let Ok::<[u8; 11], _>(bytes) = bytes.try_into() else {
+            return Err(invalid("unexpected length"));
+        };
+        if bytes[0] != Self::METADATA_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        if HashAlgorithm::from_u8(bytes[1]) != Some(H::ALGORITHM)
+            || (bytes[2] != 0) != H::DOMAIN_SEPARATED
+        {
+            return Err(invalid("hasher mismatch"));
+        }
+        let leaf_count = u64::from_le_bytes(bytes[3..].try_into().expect("length checked above"));
+        let mut range = Self {
+            root: Self::bag_peaks(&[]),
+            leaf_count: usize::try_from(leaf_count).map_err(|_| invalid("size overflow"))?,
+            tree_cache: store,
+            hasher: PhantomData,
+        };
+        range.root = Self::bag_peaks(&range.try_peaks()?);
+        Ok(range)
+    }
+    /// `version | algorithm | domain_separated | leaf_count`, the count as 8 byte little endian
+    fn save_metadata(&mut self) -> Result<(), MerkleError> {
+        let mut bytes = vec![
+            Self::METADATA_VERSION,
+            H::ALGORITHM as u8,
+            H::DOMAIN_SEPARATED as u8,
+        ];
+        bytes.extend((self.leaf_count as u64).to_le_bytes());
+        self.tree_cache.save_metadata(&bytes)
+    }
+    /// the peaks bagged from the right, the root of an empty range is the hash of no data
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+    pub fn len(&self) -> usize {
+        self.leaf_count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+    /// bags the peaks from the right, `hash(peak_0 | hash(peak_1 | ... peak_n))`
+    pub fn bag_peaks(peaks: &[Hash]) -> Hash {
+        let mut peaks = peaks.iter().rev();
+        let Some(last) = peaks.next() else {
+            return H::hash_data(&[]);
+        };
+        peaks.fold(*last, |bagged, peak| H::hash_concat(peak, &bagged))
+    }
+    /// the roots of the mountains from the left
+    pub fn peaks(&self) -> Vec<Hash> {
+        self.try_peaks().unwrap_or_default()
+    }
+    pub fn try_peaks(&self) -> Result<Vec<Hash>, MerkleError> {
+        mountains(self.leaf_count)
+            .map(|(height, index)| self.try_node(mmr_path(height, index)))
+            .collect()
+    }
+    fn try_node(&self, path: PathTrace) -> Result<Hash, MerkleError> {
+        self.tree_cache
+            .try_get(&path)?
+            .map(|node| node.data)
+            .ok_or_else(|| MerkleError::store(format!("the range is missing node {path:?}")))
+    }
+    /// panics if the store fails, see `try_append`
+    pub fn append<D: AsRef<[u8]>>(&mut self, data: &D) -> usize {
+        self.try_append(data)
+            .expect("failed to append to the range")
+    }
+    /// appends a leaf, merging it with the mountains of its height, returns its index
+    pub fn try_append<D: AsRef<[u8]>>(&mut self, data: &D) -> Result<usize, MerkleError> {
+        let leaf_index = self.leaf_count;
+        let mut node = Node::new::<H>(data, true);
+        let (mut height, mut index) = (0, leaf_index);
+        self.tree_cache.try_set(mmr_path(height, index), node)?;
+        // a right child completes a mountain of the next height
+        while index % 2 == 1 {
+            let left = self.try_node(mmr_path(height, index - 1))?;
+            node = Node {
+                data: H::hash_concat(&left, &node.data),
+                is_leaf: false,
+                from_duplicate: false,
+            };
+            height += 1;
+            index /= 2;
+            self.tree_cache.try_set(mmr_path(height, index), node)?;
+        }
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.leaf_count += 1;
+        self.root = Self::bag_peaks(&self.try_peaks()?);
+        self.save_metadata()?;
+        Ok(leaf_index)
+    }
+}
@@ -0,0 +1,89 @@
+use super::{mmr_path, mountains, MerkleMountainRange};
+use crate::hashers::Hasher;
+use crate::{Hash, MerkleError, NodeStore};
+
+/// Proof that a leaf is in a `MerkleMountainRange`, the path up to the peak of its mountain
+/// and every peak to bag into the root
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MmrProof {
+    pub leaf_index: usize,
+    /// number of leaves in the range the proof was generated from
+    pub leaf_count: usize,
+    /// the siblings from the leaf up to the peak of its mountain
+    pub siblings: Vec<Hash>,
+    /// the peaks of every mountain from the left
+    pub peaks: Vec<Hash>,
+}
+
+/// the mountain holding `leaf_index`, as (position among the mountains, height)
+fn mountain_of(leaf_index: usize, leaf_count: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    mountains(leaf_count)
+        .enumerate()
+        .find_map(|(position, (height, _))| {
+            start += 1 << height;
+            (leaf_index < start).then_some((position, height))
+        })
+}
+
+impl<Store: NodeStore, H: Hasher> MerkleMountainRange<Store, H> {
+    /// Returns the proof for the leaf at `leaf_index`
+    pub fn prove(&self, leaf_index: usize) -> Option<MmrProof> {
+        self.try_prove(leaf_index).ok().flatten()
+    }
+    pub fn try_prove(&self, leaf_index: usize) -> Result<Option<MmrProof>, MerkleError> {
+        let Some((_, height)) = mountain_of(leaf_index, self.leaf_count) else {
+            return Ok(None);
+        };
+        let siblings = (0..height)
+            .map(|level| {
+                let sibling = (leaf_index >> level) ^ 1;
+                self.tree_cache
+                    .try_get(&mmr_path(level, sibling))
+                    .map(|node| node.map(|node| node.data))
+            })
+            .collect::<Result<Option<Vec<_>>, _>>()?;
+        let Some(siblings) = siblings else {
+            return Ok(None);
+        };
+        Ok(Some(MmrProof {
+            leaf_index,
+            leaf_count: self.leaf_count,
+            siblings,
+            peaks: self.try_peaks()?,
+        }))
+    }
+    /// Verifies that the given data is the leaf at `proof.leaf_index` of the range with `leaf_count` leaves
+    /// and the given root. The sides of the siblings are computed from the leaf position.
+    /// `leaf_count` has to be a trusted one, the root doesn't commit to it, so the peaks of a smaller
+    /// claimed range could bag to the same root with the leaf at another position
+    pub fn verify_proof<D: AsRef<[u8]>>(
+        leaf_count: usize,
+        data: &D,
+        proof: &MmrProof,
+        root: &Hash,
+    ) -> bool {
+        let Some((position, height)) = mountain_of(proof.leaf_index, leaf_count) else {
+            return false;
+        };
+        if proof.leaf_count != leaf_count
+            || proof.siblings.len() != height
+            || proof.peaks.len() != proof.leaf_count.count_ones() as usize
+        {
+            return false;
+        }
+        let peak =
+            proof
+                .siblings
+                .iter()
+                .enumerate()
+                .fold(H::hash_leaf(data), |hash, (level, sibling)| {
+                    if (proof.leaf_index >> level).is_multiple_of(2) {
+                        H::hash_concat(&hash, sibling)
+                    } else {
+                        H::hash_concat(sibling, &hash)
+                    }
+                });
+        proof.peaks[position] == peak && Self::bag_peaks(&proof.peaks) == *root
+    }
+}
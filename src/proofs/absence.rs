@@ -0,0 +1,93 @@
+use crate::hashers::Hasher;
+use crate::{Hash, MerkleError, MerkleTree, NodeStore, Proof, TreeLayout};
+
+/// Proves that data is not in a tree built with `MerkleTree::construct_sorted`,
+/// with the leaves on either side of where its leaf hash would be
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AbsenceProof {
+    /// the greatest leaf hash below the absent one and its proof, `None` if there is none
+    pub left: Option<(Hash, Proof)>,
+    /// the smallest leaf hash above the absent one and its proof, `None` if there is none
+    pub right: Option<(Hash, Proof)>,
+}
+
+impl<Store: NodeStore + Send, H: Hasher> MerkleTree<Store, H> {
+    /// Returns the proofs of the two adjacent leaves bracketing the leaf hash of `data`,
+    /// `None` if the data is in the tree or the tree is padded.
+    /// Only meaningful for trees built with `construct_sorted`
+    pub fn prove_absence<D: AsRef<[u8]>>(&self, data: &D) -> Option<AbsenceProof> {
+        self.try_prove_absence(data).ok().flatten()
+    }
+    pub fn try_prove_absence<D: AsRef<[u8]>>(
+        &self,
+        data: &D,
+    ) -> Result<Option<AbsenceProof>, MerkleError> {
+        if self.layout() != TreeLayout::Unbalanced {
+            return Ok(None);
+        }
+        let target = H::hash_leaf(data);
+        let leaf = |index| -> Result<Hash, MerkleError> {
+            let path = self.path_at(self.level_count(), index);
+            self.tree_cache
+                .try_get(&path)?
+                .map(|node| node.data)
+                .ok_or(MerkleError::LeafIndexOutOfRange(index))
+        };
+        // binary search for the first leaf not below the target
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if leaf(middle)? < target {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let right = (low < self.len()).then(|| leaf(low)).transpose()?;
+        if right == Some(target) {
+            return Ok(None);
+        }
+        let bracket = |index: usize, hash: Hash| self.prove_index(index).map(|proof| (hash, proof));
+        let left = match low.checked_sub(1) {
+            Some(index) => bracket(index, leaf(index)?),
+            None => None,
+        };
+        Ok(Some(AbsenceProof {
+            left,
+            right: right.and_then(|hash| bracket(low, hash)),
+        }))
+    }
+    /// Verifies that `data` is not in the sorted tree with the given root and `leaf_count` leaves:
+    /// both leaves are proven, adjacent, (or the first or last leaf when one side is missing)
+    /// and their hashes bracket the leaf hash of the data. The root and the leaf count have to be
+    /// trusted ones of a tree built with `construct_sorted`, a proof for a smaller claimed tree
+    /// could pass interior nodes off as leaves
+    pub fn verify_absence<D: AsRef<[u8]>>(
+        leaf_count: usize,
+        data: &D,
+        proof: &AbsenceProof,
+        root_hash: &Hash,
+    ) -> bool {
+        let target = H::hash_leaf(data);
+        let proven = |side: &Option<(Hash, Proof)>| {
+            side.as_ref().is_none_or(|(leaf, proof)| {
+                Self::verify_leaf_proof(leaf_count, leaf, proof, root_hash)
+            })
+        };
+        if !proven(&proof.left) || !proven(&proof.right) {
+            return false;
+        }
+        match (&proof.left, &proof.right) {
+            (None, None) => leaf_count == 0 && root_hash == &Self::empty_root(),
+            (Some((left, left_proof)), None) => {
+                left < &target && left_proof.leaf_index + 1 == leaf_count
+            }
+            (None, Some((right, right_proof))) => &target < right && right_proof.leaf_index == 0,
+            (Some((left, left_proof)), Some((right, right_proof))) => {
+                left < &target
+                    && &target < right
+                    && left_proof.leaf_index + 1 == right_proof.leaf_index
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::hashers::Hasher;
+use crate::{get_level_count, largest_power_of_two_below, Hash, MerkleTree, NodeStore, TreeLayout};
+
+/// Proves that the tree of `old_size` leaves is a prefix of the tree of `new_size` leaves,
+/// (RFC 6962 section 2.1.2), so an audit client can detect history rewrites
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConsistencyProof {
+    pub old_size: usize,
+    pub new_size: usize,
+    /// subtree hashes in the order they are produced by the RFC 6962 `SUBPROOF` recursion
+    pub hashes: Vec<Hash>,
+}
+
+impl<Store: NodeStore + Send, H: Hasher> MerkleTree<Store, H> {
+    /// Returns a proof that the tree of `old_size` leaves is a prefix of this tree.
+    /// Only trees in the `TreeLayout::Unbalanced` layout keep earlier roots as prefixes,
+    /// (padding changes as a padded tree grows), so this is `None` for padded trees.
+    pub fn prove_consistency(&self, old_size: usize) -> Option<ConsistencyProof> {
+        if self.layout() != TreeLayout::Unbalanced || old_size == 0 || old_size > self.leaf_count {
+            return None;
+        }
+        let mut hashes = vec![];
+        self.consistency_subproof(old_size, 0, self.leaf_count, true, &mut hashes)?;
+        Some(ConsistencyProof {
+            old_size,
+            new_size: self.leaf_count,
+            hashes,
+        })
+    }
+
+    /// SUBPROOF(m, D[start..start + n], complete) from RFC 6962
+    fn consistency_subproof(
+        &self,
+        m: usize,
+        start: usize,
+        n: usize,
+        complete: bool,
+        proof: &mut Vec<Hash>,
+    ) -> Option<()> {
+        if m == n {
+            if !complete {
+                proof.push(self.subtree_hash(start, n)?);
+            }
+            return Some(());
+        }
+        let k = largest_power_of_two_below(n);
+        if m <= k {
+            self.consistency_subproof(m, start, k, complete, proof)?;
+            proof.push(self.subtree_hash(start + k, n - k)?);
+        } else {
+            self.consistency_subproof(m - k, start + k, n - k, false, proof)?;
+            proof.push(self.subtree_hash(start, k)?);
+        }
+        Some(())
+    }
+
+    /// hash of the subtree over the leaves `start..start + len`, read from the stored nodes.
+    /// `start` has to be aligned to the subtree's width and the range either complete or
+    /// ending at the last leaf, which holds for every range of the RFC 6962 recursion
+    pub(crate) fn subtree_hash(&self, start: usize, len: usize) -> Option<Hash> {
+        let depth = get_level_count(len);
+        let path = self.path_at(self.level_count() - depth, start >> depth);
+        self.tree_cache.get(&path).map(|node| node.data)
+    }
+
+    /// Verifies that `old_root` (a tree of `old_size` leaves) is a prefix of `new_root`,
+    /// (the verification algorithm from RFC 9162 section 2.1.4.2)
+    pub fn verify_consistency(
+        old_root: &Hash,
+        new_root: &Hash,
+        old_size: usize,
+        new_size: usize,
+        proof: &ConsistencyProof,
+    ) -> bool {
+        if proof.old_size != old_size || proof.new_size != new_size {
+            return false;
+        }
+        if old_size == 0 || old_size > new_size {
+            return false;
+        }
+        if old_size == new_size {
+            return proof.hashes.is_empty() && old_root == new_root;
+        }
+        // a complete old tree is itself the first subtree of the proof
+        let mut hashes = proof.hashes.iter();
+        let first = if old_size.is_power_of_two() {
+            Some(old_root)
+        } else {
+            hashes.next()
+        };
+        let Some(first) = first else {
+            return false;
+        };
+        let mut old_node = old_size - 1;
+        let mut new_node = new_size - 1;
+        while old_node & 1 == 1 {
+            old_node >>= 1;
+            new_node >>= 1;
+        }
+        let (mut old_hash, mut new_hash) = (*first, *first);
+        for next in hashes {
+            if new_node == 0 {
+                return false;
+            }
+            if old_node & 1 == 1 || old_node == new_node {
+                old_hash = H::hash_concat(next, &old_hash);
+                new_hash = H::hash_concat(next, &new_hash);
+                while old_node & 1 == 0 && old_node != 0 {
+                    old_node >>= 1;
+                    new_node >>= 1;
+                }
+            } else {
+                new_hash = H::hash_concat(&new_hash, next);
+            }
+            old_node >>= 1;
+            new_node >>= 1;
+        }
+        &old_hash == old_root && &new_hash == new_root && new_node == 0
+    }
+}
@@ -0,0 +1,225 @@
+use crate::hashers::HashAlgorithm;
+use crate::{sibling_steps, Proof};
+use std::fmt;
+
+/// version of the proof encodings produced by `Proof::to_bytes` and `Proof::to_json`
+pub const PROOF_FORMAT_VERSION: u8 = 1;
+const DOMAIN_SEPARATED_FLAG: u8 = 1;
+
+/// Errors from decoding a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofEncodingError {
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(String),
+    UnknownDirection(String),
+    UnknownFlags(u8),
+    /// the input ended before the proof was complete
+    Truncated,
+    /// bytes left after the end of the proof
+    TrailingBytes(usize),
+    /// a leaf index or tree size that doesn't fit in a usize
+    SizeOverflow(u64),
+    InvalidHex(String),
+    #[cfg(feature = "json")]
+    InvalidJson(String),
+}
+
+impl fmt::Display for ProofEncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported proof format version {version}")
+            }
+            Self::UnknownAlgorithm(algorithm) => write!(f, "unknown hash algorithm {algorithm}"),
+            Self::UnknownDirection(direction) => write!(f, "unknown hash direction {direction}"),
+            Self::UnknownFlags(flags) => write!(f, "unknown proof flags {flags:#04x}"),
+            Self::Truncated => write!(f, "proof ended unexpectedly"),
+            Self::TrailingBytes(count) => write!(f, "{count} unexpected bytes after the proof"),
+            Self::SizeOverflow(value) => write!(f, "{value} does not fit in a usize"),
+            Self::InvalidHex(hash) => write!(f, "invalid 32 byte hex hash {hash}"),
+            #[cfg(feature = "json")]
+            Self::InvalidJson(error) => write!(f, "invalid proof json: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ProofEncodingError {}
+
+/// reads fixed size fields from the front of the input
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ProofEncodingError> {
+        let (field, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .ok_or(ProofEncodingError::Truncated)?;
+        self.0 = rest;
+        Ok(*field)
+    }
+    fn u8(&mut self) -> Result<u8, ProofEncodingError> {
+        Ok(self.take::<1>()?[0])
+    }
+    fn usize(&mut self) -> Result<usize, ProofEncodingError> {
+        let value = u64::from_le_bytes(self.take()?);
+        usize::try_from(value).map_err(|_| ProofEncodingError::SizeOverflow(value))
+    }
+}
+
+impl Proof {
+    /// Encodes the proof as
+    /// `version (u8) | algorithm (u8) | flags (u8) | leaf_index (u64) | leaf_count (u64) | root (32 bytes)
+    /// | hash (32 bytes)*`, integers in little endian.
+    /// The sibling hashes go from the leaf up, their levels and directions follow from the leaf position
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(3 + 8 + 8 + 32 + self.hashes.len() * 32);
+        bytes.push(PROOF_FORMAT_VERSION);
+        bytes.push(self.algorithm as u8);
+        bytes.push(if self.domain_separated {
+            DOMAIN_SEPARATED_FLAG
+        } else {
+            0
+        });
+        bytes.extend((self.leaf_index as u64).to_le_bytes());
+        bytes.extend((self.leaf_count as u64).to_le_bytes());
+        bytes.extend(self.root);
+        for (_, _, hash) in self.hashes.iter() {
+            bytes.extend(hash);
+        }
+        bytes
+    }
+
+    /// Decodes a proof encoded with `Proof::to_bytes`, rebuilding the level and direction
+    /// of every hash from `leaf_index` and `leaf_count`, (levels count from the root at level 0)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofEncodingError> {
+        let mut reader = Reader(bytes);
+        let version = reader.u8()?;
+        if version != PROOF_FORMAT_VERSION {
+            return Err(ProofEncodingError::UnsupportedVersion(version));
+        }
+        let algorithm = reader.u8()?;
+        let algorithm = HashAlgorithm::from_u8(algorithm)
+            .ok_or(ProofEncodingError::UnknownAlgorithm(algorithm.to_string()))?;
+        let flags = reader.u8()?;
+        if flags & !DOMAIN_SEPARATED_FLAG != 0 {
+            return Err(ProofEncodingError::UnknownFlags(flags));
+        }
+        let leaf_index = reader.usize()?;
+        let leaf_count = reader.usize()?;
+        let root = reader.take()?;
+        let hashes = sibling_steps(leaf_index, leaf_count)
+            .map(|(level, direction)| Ok((level, direction, reader.take()?)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !reader.0.is_empty() {
+            return Err(ProofEncodingError::TrailingBytes(reader.0.len()));
+        }
+        Ok(Self {
+            hashes,
+            leaf_index,
+            leaf_count,
+            root,
+            algorithm,
+            domain_separated: flags & DOMAIN_SEPARATED_FLAG != 0,
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use super::{ProofEncodingError, PROOF_FORMAT_VERSION};
+    use crate::hashers::HashAlgorithm;
+    use crate::{Hash, HashDirection, Proof};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct JsonProof {
+        version: u8,
+        algorithm: String,
+        domain_separated: bool,
+        leaf_index: usize,
+        leaf_count: usize,
+        root: String,
+        hashes: Vec<JsonStep>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct JsonStep {
+        level: isize,
+        direction: String,
+        hash: String,
+    }
+
+    fn hash_from_hex(hash: &str) -> Result<Hash, ProofEncodingError> {
+        hex::decode(hash)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| ProofEncodingError::InvalidHex(hash.to_string()))
+    }
+
+    fn direction_name(direction: HashDirection) -> &'static str {
+        match direction {
+            HashDirection::Left => "left",
+            HashDirection::Right => "right",
+            HashDirection::Center => "center",
+        }
+    }
+
+    impl Proof {
+        /// Encodes the proof as json with hex encoded hashes, eg.
+        /// `{"version":1,"algorithm":"sha256","domain_separated":false,"leaf_index":0,"leaf_count":2,"root":"..","hashes":[{"level":1,"direction":"right","hash":".."}]}`
+        pub fn to_json(&self) -> String {
+            let proof = JsonProof {
+                version: PROOF_FORMAT_VERSION,
+                algorithm: self.algorithm.name().to_string(),
+                domain_separated: self.domain_separated,
+                leaf_index: self.leaf_index,
+                leaf_count: self.leaf_count,
+                root: hex::encode(self.root),
+                hashes: self
+                    .hashes
+                    .iter()
+                    .map(|(level, direction, hash)| JsonStep {
+                        level: *level,
+                        direction: direction_name(*direction).to_string(),
+                        hash: hex::encode(hash),
+                    })
+                    .collect(),
+            };
+            serde_json::to_string(&proof).expect("proofs only hold strings and integers")
+        }
+
+        /// Decodes a proof encoded with `Proof::to_json`
+        pub fn from_json(json: &str) -> Result<Self, ProofEncodingError> {
+            let proof: JsonProof = serde_json::from_str(json)
+                .map_err(|error| ProofEncodingError::InvalidJson(error.to_string()))?;
+            if proof.version != PROOF_FORMAT_VERSION {
+                return Err(ProofEncodingError::UnsupportedVersion(proof.version));
+            }
+            let algorithm = HashAlgorithm::from_name(&proof.algorithm)
+                .ok_or(ProofEncodingError::UnknownAlgorithm(proof.algorithm))?;
+            let hashes = proof
+                .hashes
+                .into_iter()
+                .map(|step| {
+                    let direction = [
+                        HashDirection::Left,
+                        HashDirection::Right,
+                        HashDirection::Center,
+                    ]
+                    .into_iter()
+                    .find(|direction| direction_name(*direction) == step.direction)
+                    .ok_or(ProofEncodingError::UnknownDirection(step.direction))?;
+                    Ok((step.level, direction, hash_from_hex(&step.hash)?))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Self {
+                hashes,
+                leaf_index: proof.leaf_index,
+                leaf_count: proof.leaf_count,
+                root: hash_from_hex(&proof.root)?,
+                algorithm,
+                domain_separated: proof.domain_separated,
+            })
+        }
+    }
+}
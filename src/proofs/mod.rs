@@ -0,0 +1,9 @@
+mod absence;
+mod consistency;
+mod encoding;
+mod multiproof;
+mod versioned;
+pub use absence::*;
+pub use consistency::*;
+pub use encoding::*;
+pub use multiproof::*;
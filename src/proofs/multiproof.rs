@@ -0,0 +1,104 @@
+use crate::hashers::Hasher;
+use crate::{Hash, MerkleTree, NodeStore};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Proves a set of leaves at once, sharing the sibling hashes of their common ancestors
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MultiProof {
+    /// indexes of the proven leaves, in the same order as the proven data
+    pub leaf_indexes: Vec<usize>,
+    /// sibling hashes that can't be computed from the proven leaves,
+    /// a level at a time from the leaves up, left to right within a level
+    pub hashes: Vec<Hash>,
+}
+
+impl<Store: NodeStore + Send, H: Hasher> MerkleTree<Store, H> {
+    /// Returns a single proof for all of the given data, with every sibling hash included only once.
+    /// `None` when any of the data is not in the tree
+    pub fn multiproof<D: AsRef<[u8]>>(&self, input: &[D]) -> Option<MultiProof> {
+        let leaf_indexes = input
+            .iter()
+            .map(|data| self.find_leaf(&H::hash_leaf(data)).ok().flatten())
+            .collect::<Option<Vec<_>>>()?;
+        self.multiproof_of(leaf_indexes)
+    }
+    /// the multiproof for the leaves at the given indexes
+    pub(crate) fn multiproof_of(&self, leaf_indexes: Vec<usize>) -> Option<MultiProof> {
+        if leaf_indexes.is_empty() || leaf_indexes.iter().any(|index| *index >= self.leaf_count) {
+            return None;
+        }
+        let mut hashes = vec![];
+        let mut pending: BTreeSet<usize> = leaf_indexes.iter().copied().collect();
+        let (mut level, mut width) = (self.level_count, self.leaf_count);
+        while width > 1 {
+            let mut parents = BTreeSet::new();
+            while let Some(index) = pending.pop_first() {
+                let sibling = index ^ 1;
+                // only siblings outside of the routes are needed,
+                // the last node of an odd level has none and is promoted, (unbalanced layout)
+                if !pending.remove(&sibling) && sibling < width {
+                    let node = self.tree_cache.get(&self.path_at(level, sibling))?;
+                    hashes.push(node.data);
+                }
+                parents.insert(index / 2);
+            }
+            pending = parents;
+            level -= 1;
+            width = width.div_ceil(2);
+        }
+        Some(MultiProof {
+            leaf_indexes,
+            hashes,
+        })
+    }
+
+    /// Verifies that all of the given data, (in the order it was proven) is at the proven indexes
+    /// of a tree with `leaf_count` leaves and the given root, `leaf_count` includes the padding for padded trees.
+    /// The positions of the nodes are derived from the indexes and the trusted `leaf_count`,
+    /// so every sibling the layout requires has to be in the proof
+    pub fn verify_multiproof<D: AsRef<[u8]>>(
+        leaf_count: usize,
+        input: &[D],
+        proof: &MultiProof,
+        root_hash: &Hash,
+    ) -> bool {
+        if input.is_empty() || input.len() != proof.leaf_indexes.len() {
+            return false;
+        }
+        let mut known: BTreeMap<usize, Hash> = BTreeMap::new();
+        for (data, &index) in input.iter().zip(proof.leaf_indexes.iter()) {
+            let hash = H::hash_leaf(data);
+            if index >= leaf_count || *known.entry(index).or_insert(hash) != hash {
+                return false;
+            }
+        }
+        let mut hashes = proof.hashes.iter();
+        let mut width = leaf_count;
+        while width > 1 {
+            let mut parents = BTreeMap::new();
+            while let Some((index, hash)) = known.pop_first() {
+                let sibling = index ^ 1;
+                let sibling_hash = match known.remove(&sibling) {
+                    Some(sibling_hash) => Some(sibling_hash),
+                    None if sibling < width => match hashes.next() {
+                        Some(sibling_hash) => Some(*sibling_hash),
+                        None => return false,
+                    },
+                    // promoted unchanged, (unbalanced layout)
+                    None => None,
+                };
+                let parent_hash = match sibling_hash {
+                    Some(sibling_hash) if index.is_multiple_of(2) => {
+                        H::hash_concat(&hash, &sibling_hash)
+                    }
+                    Some(sibling_hash) => H::hash_concat(&sibling_hash, &hash),
+                    None => hash,
+                };
+                parents.insert(index / 2, parent_hash);
+            }
+            known = parents;
+            width = width.div_ceil(2);
+        }
+        hashes.next().is_none() && known.get(&0) == Some(root_hash)
+    }
+}
@@ -0,0 +1,33 @@
+use crate::hashers::Hasher;
+use crate::stores::{VersionedPath, VersionedStore};
+use crate::{Hash, MerkleError, MerkleTree, NodeStore, Proof, TreeMetadata};
+
+impl<S: NodeStore<VersionedPath> + Send + Sync, H: Hasher> MerkleTree<VersionedStore<S>, H> {
+    /// the versions of this tree that can still be proven against, oldest first.
+    /// Construction and every mutation completes a version
+    pub fn versions(&self) -> Vec<u64> {
+        self.tree_cache.versions().collect()
+    }
+    /// the latest completed version
+    pub fn version(&self) -> Option<u64> {
+        self.tree_cache.versions().last()
+    }
+    /// the metadata of the tree when `version` was completed, `None` if it was pruned
+    pub fn metadata_at(&self, version: u64) -> Result<Option<TreeMetadata>, MerkleError> {
+        self.tree_cache
+            .metadata_at(version)
+            .map(TreeMetadata::from_bytes)
+            .transpose()
+    }
+    /// the root of the tree when `version` was completed, `None` if it was pruned
+    pub fn root_at(&self, version: u64) -> Option<Hash> {
+        Some(self.metadata_at(version).ok()??.root)
+    }
+    /// Returns the proof for the leaf at `leaf_index` of the tree when `version` was completed,
+    /// which verifies against `root_at(version)`
+    pub fn prove_at(&self, version: u64, leaf_index: usize) -> Option<Proof> {
+        let metadata = self.metadata_at(version).ok()??;
+        MerkleTree::<_, H>::with_metadata(metadata, self.tree_cache.at(version))
+            .prove_index(leaf_index)
+    }
+}
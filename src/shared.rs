@@ -0,0 +1,118 @@
+use crate::hashers::{GlobalHasher, Hasher};
+use crate::stores::StagedStore;
+use crate::{Hash, MerkleError, MerkleTree, NodeStore, Proof, TreeMetadata};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+/// A published state of a `SharedMerkleTree`, the epoch counts the applied writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeSnapshot {
+    pub epoch: u64,
+    pub metadata: TreeMetadata,
+}
+impl TreeSnapshot {
+    pub fn root(&self) -> &Hash {
+        &self.metadata.root
+    }
+}
+
+/// A `MerkleTree` shared between threads, (eg. request handlers) where readers keep proving
+/// while a writer changes the tree. A write runs against a `StagedStore` over the current nodes,
+/// then its nodes are applied and a new snapshot is published at once,
+/// so readers only wait for that and always see the nodes of the published root
+#[derive(Debug)]
+pub struct SharedMerkleTree<Store: NodeStore, H: Hasher = GlobalHasher> {
+    tree: RwLock<MerkleTree<Store, H>>,
+    snapshot: RwLock<Arc<TreeSnapshot>>,
+    /// one write is staged at a time
+    writer: Mutex<()>,
+}
+
+impl<Store: NodeStore + Send + Sync, H: Hasher> SharedMerkleTree<Store, H> {
+    pub fn new(tree: MerkleTree<Store, H>) -> Self {
+        let snapshot = TreeSnapshot {
+            epoch: 0,
+            metadata: tree.metadata(),
+        };
+        Self {
+            tree: RwLock::new(tree),
+            snapshot: RwLock::new(Arc::new(snapshot)),
+            writer: Mutex::new(()),
+        }
+    }
+    pub fn into_inner(self) -> MerkleTree<Store, H> {
+        self.tree
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+    /// the latest published state
+    pub fn snapshot(&self) -> Arc<TreeSnapshot> {
+        self.snapshot
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    pub fn root(&self) -> Hash {
+        *self.snapshot().root()
+    }
+    /// runs `read` against the published tree, writes are applied after it returns
+    pub fn read<R>(&self, read: impl FnOnce(&MerkleTree<Store, H>) -> R) -> R {
+        read(&self.tree.read().unwrap_or_else(PoisonError::into_inner))
+    }
+    /// the proof of `data` against the published root, (see `MerkleTree::prove`)
+    pub fn prove<D: AsRef<[u8]>>(&self, data: &D) -> Option<Proof> {
+        self.read(|tree| tree.prove(data))
+    }
+    pub fn prove_index(&self, leaf_index: usize) -> Option<Proof> {
+        self.read(|tree| tree.prove_index(leaf_index))
+    }
+    /// Changes the tree with `write`, eg. `shared.write(|tree| tree.try_truncate(10))`
+    /// while readers keep using the published tree. Nothing is applied if `write` fails
+    pub fn write<R, F>(&self, write: F) -> Result<R, MerkleError>
+    where
+        F: for<'a> FnOnce(&mut MerkleTree<StagedStore<'a, Store>, H>) -> Result<R, MerkleError>,
+    {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let (result, metadata, writes) = {
+            let tree = self.tree.read().unwrap_or_else(PoisonError::into_inner);
+            let store = StagedStore::new(&tree.tree_cache);
+            let mut staged = MerkleTree::with_metadata(tree.metadata(), store);
+            let result = write(&mut staged)?;
+            (result, staged.metadata(), staged.tree_cache.into_writes())
+        };
+        let mut tree = self
+            .tree
+            .write()
+            .map_err(|_| MerkleError::store("a write panicked while applying its nodes"))?;
+        tree.tree_cache.try_commit(writes)?;
+        tree.set_metadata(metadata);
+        let mut snapshot = self
+            .snapshot
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        *snapshot = Arc::new(TreeSnapshot {
+            epoch: snapshot.epoch + 1,
+            metadata,
+        });
+        Ok(result)
+    }
+    /// appends a leaf, see `MerkleTree::try_append`
+    pub fn append<D: AsRef<[u8]>>(&self, data: &D) -> Result<(), MerkleError> {
+        self.write(|tree| tree.try_append(data))
+    }
+    /// see `MerkleTree::try_append_batch`
+    pub fn append_batch<D, I>(&self, input: I) -> Result<(), MerkleError>
+    where
+        D: AsRef<[u8]> + Sync,
+        I: IntoIterator<Item = D>,
+    {
+        self.write(|tree| tree.try_append_batch(input))
+    }
+    /// see `MerkleTree::try_update_leaf`
+    pub fn update_leaf<D: AsRef<[u8]> + Sync>(
+        &self,
+        index: usize,
+        data: &D,
+    ) -> Result<Hash, MerkleError> {
+        self.write(|tree| tree.try_update_leaf(index, data))
+    }
+}
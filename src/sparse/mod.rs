@@ -0,0 +1,189 @@
+mod proof;
+use crate::hashers::{GlobalHasher, Hasher};
+use crate::stores::StoreKey;
+use crate::{Hash, MerkleError, Node, NodeStore};
+pub use proof::*;
+#[cfg(any(
+    feature = "sled",
+    feature = "all-stores",
+    feature = "rocksdb",
+    feature = "fjall"
+))]
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// number of levels below the root of a sparse tree, one for every bit of a key
+pub const SPARSE_DEPTH: usize = 256;
+
+/// Key of a node in a `SparseMerkleTree`, the first `depth` bits (most significant first)
+/// shared by every key below it, the other bits of the prefix are zero
+#[cfg_attr(
+    any(
+        feature = "sled",
+        feature = "rocksdb",
+        feature = "all-stores",
+        feature = "fjall"
+    ),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, std::hash::Hash)]
+pub struct SparsePath {
+    pub depth: u16,
+    pub prefix: Hash,
+}
+impl SparsePath {
+    pub fn root() -> Self {
+        Self::default()
+    }
+    /// the node at `depth` on the path from the root to the leaf of `key`
+    pub fn new(key: &Hash, depth: usize) -> Self {
+        let mut prefix = *key;
+        for (index, byte) in prefix.iter_mut().enumerate() {
+            let kept = depth.saturating_sub(index * 8).min(8);
+            *byte &= !(0xff_u8.checked_shr(kept as u32).unwrap_or(0));
+        }
+        Self {
+            depth: depth as u16,
+            prefix,
+        }
+    }
+    /// the other child of this node's parent
+    pub fn sibling(&self) -> Self {
+        let bit = self.depth as usize - 1;
+        let mut prefix = self.prefix;
+        prefix[bit / 8] ^= 0x80 >> (bit % 8);
+        Self { prefix, ..*self }
+    }
+}
+// the sparse tree finds its nodes by key only
+impl StoreKey for SparsePath {
+    const HASH_LOOKUP: bool = false;
+}
+/// whether the path to `key` goes right at `depth`, (bit `depth` of the key)
+pub fn key_bit(key: &Hash, depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// A sparse Merkle tree over every 256-bit key, (eg. for key→value state commitments).
+/// Only the nodes above keys with a value are stored, empty subtrees hash to precomputed defaults.
+/// Like `MerkleTree` it stores hashes, a key holds the leaf hash `H::hash_leaf(&value)`
+#[derive(Debug)]
+pub struct SparseMerkleTree<Store: NodeStore<SparsePath>, H: Hasher = GlobalHasher> {
+    root: Hash,
+    /// the hash of an empty subtree with its root at each depth, the last one is an empty leaf
+    defaults: Vec<Hash>,
+    pub tree_cache: Store,
+    hasher: PhantomData<H>,
+}
+
+// constructors using the default hasher, (type parameter defaults are not used for inference)
+impl<Store: NodeStore<SparsePath>> SparseMerkleTree<Store> {
+    /// an empty tree, or the tree already in `store`
+    pub fn new(store: Store) -> Result<Self, MerkleError> {
+        Self::with_hasher(store)
+    }
+}
+
+impl<Store: NodeStore<SparsePath>, H: Hasher> SparseMerkleTree<Store, H> {
+    /// an empty tree hashed with `H`, or the tree already in `store`
+    pub fn with_hasher(store: Store) -> Result<Self, MerkleError> {
+        let defaults = Self::default_hashes();
+        let root = store
+            .try_get(&SparsePath::root())?
+            .map_or(defaults[0], |node| node.data);
+        Ok(Self {
+            root,
+            defaults,
+            tree_cache: store,
+            hasher: PhantomData,
+        })
+    }
+    /// the hash of an empty subtree with its root at every depth, from the root down to a leaf.
+    /// An empty leaf is all zeros, so no value can hash to it
+    pub fn default_hashes() -> Vec<Hash> {
+        let mut defaults = vec![[0; 32]; SPARSE_DEPTH + 1];
+        for depth in (0..SPARSE_DEPTH).rev() {
+            defaults[depth] = H::hash_concat(&defaults[depth + 1], &defaults[depth + 1]);
+        }
+        defaults
+    }
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+    /// the root of a tree without values
+    pub fn empty_root() -> Hash {
+        Self::default_hashes()[0]
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root == self.defaults[0]
+    }
+    /// the leaf hash at `key`, `None` if it holds no value
+    pub fn get(&self, key: &Hash) -> Option<Hash> {
+        self.try_get(key).ok().flatten()
+    }
+    pub fn try_get(&self, key: &Hash) -> Result<Option<Hash>, MerkleError> {
+        Ok(self
+            .tree_cache
+            .try_get(&SparsePath::new(key, SPARSE_DEPTH))?
+            .map(|node| node.data))
+    }
+    /// panics if the store fails, see `try_insert`
+    pub fn insert<D: AsRef<[u8]>>(&mut self, key: Hash, value: &D) -> Option<Hash> {
+        self.try_insert(key, value)
+            .expect("failed to insert into the tree")
+    }
+    /// sets the value of `key`, returning the leaf hash it replaced
+    pub fn try_insert<D: AsRef<[u8]>>(
+        &mut self,
+        key: Hash,
+        value: &D,
+    ) -> Result<Option<Hash>, MerkleError> {
+        self.set_leaf(&key, Some(H::hash_leaf(value)))
+    }
+    /// panics if the store fails, see `try_remove`
+    pub fn remove(&mut self, key: &Hash) -> Option<Hash> {
+        self.try_remove(key)
+            .expect("failed to remove from the tree")
+    }
+    /// clears the value of `key`, returning its leaf hash
+    pub fn try_remove(&mut self, key: &Hash) -> Result<Option<Hash>, MerkleError> {
+        self.set_leaf(key, None)
+    }
+    /// the hash of the node at `path`, the default hash if its subtree is empty
+    fn node_hash(&self, path: &SparsePath) -> Result<Hash, MerkleError> {
+        Ok(self
+            .tree_cache
+            .try_get(path)?
+            .map_or(self.defaults[path.depth as usize], |node| node.data))
+    }
+    /// writes the leaf of `key` and rehashes its path to the root,
+    /// nodes that become empty are removed instead of storing their default hash
+    fn set_leaf(&mut self, key: &Hash, leaf: Option<Hash>) -> Result<Option<Hash>, MerkleError> {
+        let old = self.try_get(key)?;
+        let mut hash = leaf.unwrap_or(self.defaults[SPARSE_DEPTH]);
+        for depth in (0..=SPARSE_DEPTH).rev() {
+            let path = SparsePath::new(key, depth);
+            if hash == self.defaults[depth] {
+                self.tree_cache.try_remove_node(path)?;
+            } else {
+                let node = Node {
+                    is_leaf: depth == SPARSE_DEPTH,
+                    data: hash,
+                    from_duplicate: false,
+                };
+                self.tree_cache.try_set(path, node)?;
+            }
+            if depth > 0 {
+                let sibling = self.node_hash(&path.sibling())?;
+                hash = if key_bit(key, depth - 1) {
+                    H::hash_concat(&sibling, &hash)
+                } else {
+                    H::hash_concat(&hash, &sibling)
+                };
+            }
+        }
+        self.tree_cache.try_trigger_batch_actions()?;
+        self.root = hash;
+        Ok(old)
+    }
+}
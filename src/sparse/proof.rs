@@ -0,0 +1,68 @@
+use super::{key_bit, SparseMerkleTree, SparsePath, SPARSE_DEPTH};
+use crate::hashers::Hasher;
+use crate::{Hash, MerkleError, NodeStore};
+
+/// The siblings on the path of a key in a `SparseMerkleTree`, proving either the value
+/// the key holds, (inclusion) or that it holds none, (non-inclusion)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SparseProof {
+    pub key: Hash,
+    /// bit `depth - 1` is set when the sibling at `depth` is not an empty subtree
+    pub bitmap: Hash,
+    /// the siblings that are not empty subtrees, from the leaf up to the root
+    pub siblings: Vec<Hash>,
+}
+
+impl<Store: NodeStore<SparsePath>, H: Hasher> SparseMerkleTree<Store, H> {
+    /// panics if the store fails, see `try_prove`
+    pub fn prove(&self, key: &Hash) -> SparseProof {
+        self.try_prove(key).expect("failed to read the tree")
+    }
+    /// the proof for `key`, whether it holds a value or not
+    pub fn try_prove(&self, key: &Hash) -> Result<SparseProof, MerkleError> {
+        let mut proof = SparseProof {
+            key: *key,
+            ..Default::default()
+        };
+        for depth in (1..=SPARSE_DEPTH).rev() {
+            let sibling = SparsePath::new(key, depth).sibling();
+            if let Some(node) = self.tree_cache.try_get(&sibling)? {
+                proof.bitmap[(depth - 1) / 8] |= 0x80 >> ((depth - 1) % 8);
+                proof.siblings.push(node.data);
+            }
+        }
+        Ok(proof)
+    }
+    /// Verifies that `key` holds `value` in the tree with the given root
+    pub fn verify_inclusion<D: AsRef<[u8]>>(
+        key: &Hash,
+        value: &D,
+        proof: &SparseProof,
+        root: &Hash,
+    ) -> bool {
+        proof.key == *key && Self::root_from_proof(H::hash_leaf(value), proof) == Some(*root)
+    }
+    /// Verifies that `key` holds no value in the tree with the given root
+    pub fn verify_non_inclusion(key: &Hash, proof: &SparseProof, root: &Hash) -> bool {
+        proof.key == *key && Self::root_from_proof([0; 32], proof) == Some(*root)
+    }
+    /// hashes `leaf` up with the siblings in the proof, `None` if it has too few or too many
+    fn root_from_proof(leaf: Hash, proof: &SparseProof) -> Option<Hash> {
+        let defaults = Self::default_hashes();
+        let mut siblings = proof.siblings.iter();
+        let mut hash = leaf;
+        for depth in (1..=SPARSE_DEPTH).rev() {
+            let sibling = if key_bit(&proof.bitmap, depth - 1) {
+                *siblings.next()?
+            } else {
+                defaults[depth]
+            };
+            hash = if key_bit(&proof.key, depth - 1) {
+                H::hash_concat(&sibling, &hash)
+            } else {
+                H::hash_concat(&hash, &sibling)
+            };
+        }
+        siblings.next().is_none().then_some(hash)
+    }
+}
@@ -0,0 +1,285 @@
+use super::{NodeStore, StagedWrites, StoreType};
+use crate::{Hash, MerkleError, Node, PathTrace};
+use std::future::Future;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Non-blocking storage for the nodes of a tree, keyed by `K`, (see `NodeStore`).
+/// Backends with a native async client implement it directly,
+/// the existing stores run on tokio's blocking thread pool with `Blocking`
+pub trait AsyncNodeStore<K = PathTrace>: Send + Sync {
+    /// a synchronous view of the same nodes, (eg. the blocking client of the backend).
+    /// `AsyncMerkleTree` runs the operations of `MerkleTree` against it on the blocking thread pool,
+    /// staging their writes for `commit`
+    type Reads: NodeStore<K> + Send + Sync + 'static;
+    fn reads(&self) -> Self::Reads;
+    fn get(&self, key: K) -> impl Future<Output = Result<Option<Node>, MerkleError>> + Send;
+    fn get_key_by_hash(
+        &self,
+        hash: Hash,
+    ) -> impl Future<Output = Result<Option<K>, MerkleError>> + Send;
+    /// add new values to the store, (this could also be scheduling a batch insert)
+    fn set(&mut self, key: K, value: Node) -> impl Future<Output = Result<(), MerkleError>> + Send;
+    fn set_many(
+        &mut self,
+        nodes: Vec<(K, Node)>,
+    ) -> impl Future<Output = Result<(), MerkleError>> + Send
+    where
+        K: Send,
+    {
+        async move {
+            for (key, node) in nodes {
+                self.set(key, node).await?;
+            }
+            Ok(())
+        }
+    }
+    fn remove_node(&mut self, key: K) -> impl Future<Output = Result<(), MerkleError>> + Send;
+    fn trigger_batch_actions(&mut self) -> impl Future<Output = Result<(), MerkleError>> + Send;
+    /// sort the items by value, for store that support binary_search by value
+    fn sort(&mut self) -> impl Future<Output = Result<(), MerkleError>> + Send;
+    /// persist the tree's metadata next to its nodes, (see `TreeMetadata`)
+    fn save_metadata(
+        &mut self,
+        metadata: Vec<u8>,
+    ) -> impl Future<Output = Result<(), MerkleError>> + Send;
+    fn load_metadata(&self) -> impl Future<Output = Result<Option<Vec<u8>>, MerkleError>> + Send;
+    /// the number of nodes `AsyncMerkleTree::construct` commits at a time,
+    /// so a large tree isn't built in memory before it's written
+    fn batch_size(&self) -> usize {
+        1 << 14
+    }
+    /// writes the nodes and metadata staged by a tree operation, (see `StagedWrites::apply`)
+    /// stores that can write them in one batch override this
+    fn commit(
+        &mut self,
+        writes: StagedWrites<K>,
+    ) -> impl Future<Output = Result<(), MerkleError>> + Send
+    where
+        K: Copy + Eq + std::hash::Hash + Send,
+    {
+        async move {
+            // replaced nodes are removed first, so the store drops the lookups of their old hashes
+            let mut sets = Vec::with_capacity(writes.nodes.len());
+            for (key, node) in writes.nodes {
+                let old = self.get(key).await?;
+                match node {
+                    Some(node) if old.is_some_and(|old| old.data == node.data) => {
+                        sets.push((key, node))
+                    }
+                    Some(node) => {
+                        if old.is_some() {
+                            self.remove_node(key).await?;
+                        }
+                        sets.push((key, node));
+                    }
+                    None if old.is_some() => self.remove_node(key).await?,
+                    None => {}
+                }
+            }
+            self.set_many(sets).await?;
+            self.trigger_batch_actions().await?;
+            if let Some(metadata) = writes.metadata {
+                self.save_metadata(metadata).await?;
+            }
+            self.sort().await
+        }
+    }
+}
+
+/// Runs a synchronous store on tokio's blocking thread pool, so reads and writes
+/// don't stall the executor, eg. `Blocking::new(SledStore::new(&db, "tree")?)`.
+/// Clones share the store
+#[derive(Debug, Default)]
+pub struct Blocking<S> {
+    store: Arc<RwLock<S>>,
+}
+impl<S> Clone for Blocking<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<S> Blocking<S> {
+    fn lock(&self) -> Result<RwLockReadGuard<'_, S>, MerkleError> {
+        self.store
+            .read()
+            .map_err(|_| MerkleError::store("poisoned store lock"))
+    }
+    fn lock_mut(&self) -> Result<RwLockWriteGuard<'_, S>, MerkleError> {
+        self.store
+            .write()
+            .map_err(|_| MerkleError::store("poisoned store lock"))
+    }
+}
+
+impl<S: Send + Sync + 'static> Blocking<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(store)),
+        }
+    }
+    /// runs `read` with the store on the blocking thread pool
+    pub async fn read<R, F>(&self, read: F) -> Result<R, MerkleError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&S) -> Result<R, MerkleError> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || read(&*store.lock()?))
+            .await
+            .map_err(MerkleError::store)?
+    }
+    /// runs `write` with the store on the blocking thread pool
+    pub async fn write<R, F>(&self, write: F) -> Result<R, MerkleError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut S) -> Result<R, MerkleError> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || write(&mut *store.lock_mut()?))
+            .await
+            .map_err(MerkleError::store)?
+    }
+}
+
+impl<K, S> AsyncNodeStore<K> for Blocking<S>
+where
+    K: Send + 'static,
+    S: NodeStore<K> + Send + Sync + 'static,
+{
+    // the store is only locked for each access, so reads of a running tree operation
+    // can go straight to it from the blocking thread pool
+    type Reads = Self;
+    fn reads(&self) -> Self {
+        self.clone()
+    }
+    async fn get(&self, key: K) -> Result<Option<Node>, MerkleError> {
+        self.read(move |store| store.try_get(&key)).await
+    }
+    async fn get_key_by_hash(&self, hash: Hash) -> Result<Option<K>, MerkleError> {
+        self.read(move |store| store.try_get_key_by_hash(&hash))
+            .await
+    }
+    async fn set(&mut self, key: K, value: Node) -> Result<(), MerkleError> {
+        self.write(move |store| store.try_set(key, value).map(|_| ()))
+            .await
+    }
+    async fn set_many(&mut self, nodes: Vec<(K, Node)>) -> Result<(), MerkleError> {
+        self.write(move |store| {
+            store.reserve(nodes.len());
+            nodes
+                .into_iter()
+                .try_for_each(|(key, node)| store.try_set(key, node).map(|_| ()))
+        })
+        .await
+    }
+    async fn remove_node(&mut self, key: K) -> Result<(), MerkleError> {
+        self.write(move |store| store.try_remove_node(key)).await
+    }
+    async fn trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
+        self.write(|store| store.try_trigger_batch_actions()).await
+    }
+    async fn sort(&mut self) -> Result<(), MerkleError> {
+        self.write(|store| {
+            store.sort();
+            Ok(())
+        })
+        .await
+    }
+    async fn save_metadata(&mut self, metadata: Vec<u8>) -> Result<(), MerkleError> {
+        self.write(move |store| store.save_metadata(&metadata))
+            .await
+    }
+    async fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        self.read(|store| store.load_metadata()).await
+    }
+    async fn commit(&mut self, writes: StagedWrites<K>) -> Result<(), MerkleError>
+    where
+        K: Copy + Eq + std::hash::Hash + Send,
+    {
+        self.write(move |store| store.try_commit(writes)).await
+    }
+}
+
+// the synchronous access to a shared store, locking it for each call.
+// Following `NodeStore`, the infallible variants panic on a failed write and read a failing node as missing
+impl<K, S: NodeStore<K>> NodeStore<K> for Blocking<S> {
+    fn store_type(&self) -> StoreType {
+        self.lock().expect("poisoned store lock").store_type()
+    }
+    fn set(&mut self, key: K, value: Node) -> Option<Node> {
+        self.try_set(key, value)
+            .expect("failed to write to the shared store")
+    }
+    fn get(&self, key: &K) -> Option<Node> {
+        self.try_get(key).ok().flatten()
+    }
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<K> {
+        self.try_get_key_by_hash(hash).ok().flatten()
+    }
+    fn sort(&mut self) {
+        self.lock_mut().expect("poisoned store lock").sort();
+    }
+    fn exists(&self, key: &K) -> bool {
+        self.try_exists(key).unwrap_or(false)
+    }
+    fn reserve(&mut self, items: usize) {
+        self.lock_mut().expect("poisoned store lock").reserve(items);
+    }
+    fn update_value(&mut self, key: &K, next_value: Node) {
+        self.try_update_value(key, next_value)
+            .expect("failed to write to the shared store")
+    }
+    fn entries(&self) -> impl Iterator<Item = (K, Node)> {
+        let entries: Vec<_> = match self.lock() {
+            Ok(store) => store.entries().collect(),
+            Err(_) => vec![],
+        };
+        entries.into_iter()
+    }
+    fn trigger_batch_actions(&mut self) {
+        self.try_trigger_batch_actions()
+            .expect("failed to write to the shared store")
+    }
+    fn remove_node(&mut self, key: K) {
+        self.try_remove_node(key)
+            .expect("failed to write to the shared store")
+    }
+    fn try_set(&mut self, key: K, value: Node) -> Result<Option<Node>, MerkleError> {
+        self.lock_mut()?.try_set(key, value)
+    }
+    fn try_get(&self, key: &K) -> Result<Option<Node>, MerkleError> {
+        self.lock()?.try_get(key)
+    }
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<K>, MerkleError> {
+        self.lock()?.try_get_key_by_hash(hash)
+    }
+    fn try_exists(&self, key: &K) -> Result<bool, MerkleError> {
+        self.lock()?.try_exists(key)
+    }
+    fn try_update_value(&mut self, key: &K, next_value: Node) -> Result<(), MerkleError> {
+        self.lock_mut()?.try_update_value(key, next_value)
+    }
+    fn try_trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
+        self.lock_mut()?.try_trigger_batch_actions()
+    }
+    fn try_remove_node(&mut self, key: K) -> Result<(), MerkleError> {
+        self.lock_mut()?.try_remove_node(key)
+    }
+    fn save_metadata(&mut self, metadata: &[u8]) -> Result<(), MerkleError> {
+        self.lock_mut()?.save_metadata(metadata)
+    }
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        self.lock()?.load_metadata()
+    }
+    fn try_commit(&mut self, writes: StagedWrites<K>) -> Result<(), MerkleError>
+    where
+        Self: Sized,
+        K: Copy + Eq + std::hash::Hash,
+    {
+        let mut store = self.lock_mut()?;
+        store.try_commit(writes)
+    }
+}
@@ -1,90 +1,87 @@
-use crate::{Node, NodeStore, PathTrace};
+use crate::stores::{StagedWrites, StoreKey};
+use crate::{Hash, MerkleError, Node, NodeStore, PathTrace};
 use fjall::{Batch, Config, Error, Keyspace, Partition, PartitionCreateOptions, Slice};
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
 use std::sync::Arc;
 use std::sync::Mutex;
+const METADATA_KEY: &[u8] = b"tree";
+/// nodes keyed by `K` in a fjall partition, (see `NodeStore`)
 #[derive(Clone)]
-pub struct FjallDbStore<'a> {
+pub struct FjallDbStore<'a, K = PathTrace> {
     db: &'a Keyspace,
     node_store: Partition,    // (path_trace, Node)
     hash_key_tree: Partition, // (hash, path_trace)
+    meta_tree: Partition,     // tree metadata, (see `TreeMetadata`)
     node_store_batch: Arc<Mutex<Batch>>,
     hash_key_tree_batch: Arc<Mutex<Batch>>,
+    key: PhantomData<K>,
 }
 
 impl<'a> FjallDbStore<'a> {
     pub fn new(db: &'a Keyspace, name: impl AsRef<str>) -> Result<Self, Error> {
+        Self::new_keyed(db, name)
+    }
+}
+impl<'a, K> FjallDbStore<'a, K> {
+    /// a store for nodes keyed by `K`, eg. `FjallDbStore::<SparsePath>::new_keyed(&db, "sparse")`
+    pub fn new_keyed(db: &'a Keyspace, name: impl AsRef<str>) -> Result<Self, Error> {
         let name = name.as_ref();
         let look_up = format!("{name}-lookup");
+        let meta = format!("{name}-meta");
         let node_store = db.open_partition(name, PartitionCreateOptions::default())?;
 
         let hash_key_tree = db.open_partition(&look_up, PartitionCreateOptions::default())?;
+        let meta_tree = db.open_partition(&meta, PartitionCreateOptions::default())?;
         let node_store_batch = Arc::new(Mutex::new(db.batch()));
         let hash_key_tree_batch = Arc::new(Mutex::new(db.batch()));
         Ok(Self {
             db,
             node_store,
             hash_key_tree,
+            meta_tree,
             node_store_batch,
             hash_key_tree_batch,
+            key: PhantomData,
         })
     }
 }
 
-impl NodeStore for FjallDbStore<'_> {
+impl<K: StoreKey + Serialize + DeserializeOwned> NodeStore<K> for FjallDbStore<'_, K> {
     fn store_type(&self) -> super::StoreType {
         super::StoreType::Fjall
     }
-    fn set(&mut self, key: PathTrace, value: Node) -> Option<Node> {
-        let path: Vec<_> = bincode::serialize(&key).ok()?;
-        let node: Vec<_> = bincode::serialize(&value).ok()?;
-        let hash = value.data;
-        self.node_store_batch
-            .lock()
-            .unwrap()
-            .insert(&self.node_store, &path, node);
-        // skip updating this for duplicates
-        if !self.hash_key_tree.contains_key(hash).unwrap_or_default() {
-            let _ = self.hash_key_tree.insert(hash, path);
-        }
-        Some(value)
+    fn set(&mut self, key: K, value: Node) -> Option<Node> {
+        self.try_set(key, value)
+            .expect("fjall failed to set the node")
     }
 
-    fn get(&self, key: &PathTrace) -> Option<Node> {
-        let path: Vec<u8> = bincode::serialize(&key).ok()?;
-        self.node_store
-            .get(&path)
-            .ok()?
-            .and_then(|v| bincode::deserialize(&v).ok())
+    fn get(&self, key: &K) -> Option<Node> {
+        self.try_get(key).ok().flatten()
     }
 
-    fn get_key_by_hash(&self, hash: &crate::Hash) -> Option<PathTrace> {
-        let key: Vec<u8> = bincode::serialize(&hash).ok()?;
-        if let Some(path_bytes) = self.hash_key_tree.get(key).ok()? {
-            return bincode::deserialize(&path_bytes).ok();
-        }
-
-        None
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<K> {
+        self.try_get_key_by_hash(hash).ok().flatten()
     }
 
     fn sort(&mut self) {
         // not required
     }
 
-    fn exists(&self, key: &PathTrace) -> bool {
-        let key: Vec<u8> = bincode::serialize(&key).unwrap();
-        self.node_store.contains_key(key).ok().unwrap_or_default()
+    fn exists(&self, key: &K) -> bool {
+        self.try_exists(key).unwrap_or_default()
     }
 
     fn reserve(&mut self, _items: usize) {
         // not required
     }
 
-    fn update_value(&mut self, key: &PathTrace, next_value: Node) {
+    fn update_value(&mut self, key: &K, next_value: Node) {
         self.set(*key, next_value);
         self.trigger_batch_actions();
     }
 
-    fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)> {
+    fn entries(&self) -> impl Iterator<Item = (K, Node)> {
         self.node_store.iter().flatten().flat_map(|(key, value)| {
             if let (Ok(path), Ok(node)) = (
                 bincode::deserialize(key.as_ref()),
@@ -98,26 +95,152 @@ impl NodeStore for FjallDbStore<'_> {
     }
 
     fn trigger_batch_actions(&mut self) {
-        let node_store_batch =
-            std::mem::replace(&mut *self.node_store_batch.lock().unwrap(), self.db.batch());
+        self.try_trigger_batch_actions()
+            .expect("fjall failed to write the batch")
+    }
+
+    fn remove_node(&mut self, key: K) {
+        self.try_remove_node(key)
+            .expect("fjall failed to remove the node")
+    }
+
+    fn try_set(&mut self, key: K, value: Node) -> Result<Option<Node>, MerkleError> {
+        let path: Vec<_> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        let node: Vec<_> = bincode::serialize(&value).map_err(MerkleError::serialization)?;
+        let hash = value.data;
+        self.node_store_batch
+            .lock()
+            .map_err(|_| MerkleError::store("the node batch lock is poisoned"))?
+            .insert(&self.node_store, &path, node);
+        // skip updating this for duplicates
+        if K::HASH_LOOKUP
+            && !self
+                .hash_key_tree
+                .contains_key(hash)
+                .map_err(MerkleError::store)?
+        {
+            self.hash_key_tree
+                .insert(hash, path)
+                .map_err(MerkleError::store)?;
+        }
+        Ok(Some(value))
+    }
+
+    fn try_get(&self, key: &K) -> Result<Option<Node>, MerkleError> {
+        let path: Vec<u8> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        self.node_store
+            .get(&path)
+            .map_err(MerkleError::store)?
+            .map(|v| bincode::deserialize(&v).map_err(MerkleError::serialization))
+            .transpose()
+    }
+
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<K>, MerkleError> {
+        let key: Vec<u8> = bincode::serialize(&hash).map_err(MerkleError::serialization)?;
+        self.hash_key_tree
+            .get(key)
+            .map_err(MerkleError::store)?
+            .map(|path_bytes| bincode::deserialize(&path_bytes).map_err(MerkleError::serialization))
+            .transpose()
+    }
+
+    fn try_exists(&self, key: &K) -> Result<bool, MerkleError> {
+        let key: Vec<u8> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        self.node_store
+            .contains_key(key)
+            .map_err(MerkleError::store)
+    }
+
+    fn try_update_value(&mut self, key: &K, next_value: Node) -> Result<(), MerkleError> {
+        self.try_set(*key, next_value)?;
+        self.try_trigger_batch_actions()
+    }
+
+    fn try_trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
+        let poisoned = |_| MerkleError::store("the node batch lock is poisoned");
+        let node_store_batch = std::mem::replace(
+            &mut *self.node_store_batch.lock().map_err(poisoned)?,
+            self.db.batch(),
+        );
         let hash_key_tree_batch = std::mem::replace(
-            &mut *self.hash_key_tree_batch.lock().unwrap(),
+            &mut *self.hash_key_tree_batch.lock().map_err(poisoned)?,
             self.db.batch(),
         );
-        node_store_batch.commit().expect("failed to insert");
-        hash_key_tree_batch.commit().expect("failed to insert")
-    }
-
-    fn remove_node(&mut self, key: PathTrace) {
-        if let Ok(key_v) = bincode::serialize(&key) {
-            if let Some(node) = self.get(&key) {
-                if self.node_store.remove(key_v).is_ok() {
-                    self.hash_key_tree
-                        .remove(node.data)
-                        .expect("fjall failed to remove")
-                }
+        node_store_batch.commit().map_err(MerkleError::store)?;
+        hash_key_tree_batch.commit().map_err(MerkleError::store)
+    }
+
+    fn try_remove_node(&mut self, key: K) -> Result<(), MerkleError> {
+        let key_v = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        if let Some(node) = self.try_get(&key)? {
+            self.node_store.remove(&key_v).map_err(MerkleError::store)?;
+            if !K::HASH_LOOKUP {
+                return Ok(());
             }
+            // keep the lookup if it points at another node with the same hash
+            let lookup = self
+                .hash_key_tree
+                .get(node.data)
+                .map_err(MerkleError::store)?;
+            if lookup.is_some_and(|path| path.as_ref() == key_v.as_slice()) {
+                self.hash_key_tree
+                    .remove(node.data)
+                    .map_err(MerkleError::store)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save_metadata(&mut self, metadata: &[u8]) -> Result<(), MerkleError> {
+        self.meta_tree
+            .insert(METADATA_KEY, metadata)
+            .map_err(MerkleError::store)
+    }
+
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self
+            .meta_tree
+            .get(METADATA_KEY)
+            .map_err(MerkleError::store)?
+            .map(|bytes| bytes.to_vec()))
+    }
+
+    /// commits the writes in one batch over the nodes, lookups and metadata
+    fn try_commit(&mut self, writes: StagedWrites<K>) -> Result<(), MerkleError>
+    where
+        K: Copy + Eq + std::hash::Hash,
+    {
+        self.try_trigger_batch_actions()?;
+        let raw = writes.into_raw(
+            |key| {
+                self.node_store
+                    .get(key)
+                    .map_err(MerkleError::store)?
+                    .map(|node| bincode::deserialize(&node).map_err(MerkleError::serialization))
+                    .transpose()
+            },
+            |hash| {
+                let lookup = self.hash_key_tree.get(hash).map_err(MerkleError::store)?;
+                Ok(lookup.map(|path| path.to_vec()))
+            },
+        )?;
+        let mut batch = self.db.batch();
+        for key in raw.removed_nodes {
+            batch.remove(&self.node_store, key);
+        }
+        for (key, node) in raw.nodes {
+            batch.insert(&self.node_store, key, node);
+        }
+        for hash in raw.removed_lookups {
+            batch.remove(&self.hash_key_tree, hash);
+        }
+        for (hash, key) in raw.lookups {
+            batch.insert(&self.hash_key_tree, hash, key);
+        }
+        if let Some(metadata) = raw.metadata {
+            batch.insert(&self.meta_tree, METADATA_KEY, metadata);
         }
+        batch.commit().map_err(MerkleError::store)
     }
 }
 pub fn temporary_fjall_db() -> Keyspace {
@@ -1,11 +1,17 @@
-use crate::HashDirection;
+use crate::MerkleError;
+#[cfg(feature = "async")]
+mod async_store;
 #[cfg(feature = "fjall")]
 mod fjall_storage;
 #[cfg(feature = "rocksdb")]
 mod rocksdb_storage;
 #[cfg(feature = "sled")]
 mod sled_storage;
-use super::{Hash, Node, PathTrace};
+mod staged;
+mod versioned;
+use super::{Hash, HashDirection, Node, PathTrace};
+#[cfg(feature = "async")]
+pub use async_store::*;
 #[cfg(feature = "fjall")]
 pub use fjall_storage::*;
 use indexmap::IndexMap;
@@ -14,6 +20,8 @@ use itertools::Itertools;
 pub use rocksdb_storage::*;
 #[cfg(feature = "sled")]
 pub use sled_storage::*;
+pub use staged::*;
+pub use versioned::*;
 #[derive(Debug, Clone, Copy)]
 pub enum StoreType {
     Sled,
@@ -21,31 +29,80 @@ pub enum StoreType {
     IndexMap,
     Fjall,
 }
-pub trait NodeStore {
+/// Storage for the nodes of a tree, keyed by `K`.
+/// The dense `MerkleTree` keys its nodes by `PathTrace`, the `SparseMerkleTree` by `SparsePath`
+pub trait NodeStore<K = PathTrace> {
     fn store_type(&self) -> StoreType {
         StoreType::IndexMap
     }
-    /// change the direction of the current root from (level, Center, 0) to (level, left, 0)
-    fn shift_root_to_left(&mut self, lowest_level: isize) {
-        let mut root_path = PathTrace::root(lowest_level);
-        if let Some(root_node) = self.get(&root_path) {
-            self.remove_node(root_path);
-            root_path.direction = HashDirection::Left;
-            let _ = self.set(root_path, root_node);
-        }
-    }
+    // ------------------------- INFALLIBLE VARIANTS ------------------------------------------------
+    // stores that can fail panic with a message when a write fails, (set, update_value,
+    // trigger_batch_actions and remove_node) and read a failing node as missing
     /// add new values to the store, (this could also be scheduling a batch insert)
-    fn set(&mut self, key: PathTrace, value: Node) -> Option<Node>;
-    fn get(&self, key: &PathTrace) -> Option<Node>;
-    fn get_key_by_hash(&self, hash: &Hash) -> Option<PathTrace>;
+    fn set(&mut self, key: K, value: Node) -> Option<Node>;
+    fn get(&self, key: &K) -> Option<Node>;
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<K>;
     /// sort the items by value, for store that support binary_search by value
     fn sort(&mut self);
-    fn exists(&self, key: &PathTrace) -> bool;
+    fn exists(&self, key: &K) -> bool;
     fn reserve(&mut self, items: usize);
-    fn update_value(&mut self, key: &PathTrace, next_value: Node);
-    fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)>;
+    fn update_value(&mut self, key: &K, next_value: Node);
+    fn entries(&self) -> impl Iterator<Item = (K, Node)>;
     fn trigger_batch_actions(&mut self);
-    fn remove_node(&mut self, key: PathTrace);
+    fn remove_node(&mut self, key: K);
+    // ------------------------- FALLIBLE VARIANTS --------------------------------------------------
+    // stores that can fail, (eg. on a full disk) override these to report the error
+    // instead of panicking or dropping it, the tree only calls these internally
+    fn try_set(&mut self, key: K, value: Node) -> Result<Option<Node>, MerkleError> {
+        Ok(self.set(key, value))
+    }
+    fn try_get(&self, key: &K) -> Result<Option<Node>, MerkleError> {
+        Ok(self.get(key))
+    }
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<K>, MerkleError> {
+        Ok(self.get_key_by_hash(hash))
+    }
+    fn try_exists(&self, key: &K) -> Result<bool, MerkleError> {
+        Ok(self.exists(key))
+    }
+    fn try_update_value(&mut self, key: &K, next_value: Node) -> Result<(), MerkleError> {
+        self.update_value(key, next_value);
+        Ok(())
+    }
+    fn try_trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
+        self.trigger_batch_actions();
+        Ok(())
+    }
+    fn try_remove_node(&mut self, key: K) -> Result<(), MerkleError> {
+        self.remove_node(key);
+        Ok(())
+    }
+    /// persist the tree's metadata next to its nodes, (see `TreeMetadata`)
+    /// stores that don't outlive the process don't need to keep it
+    fn save_metadata(&mut self, _metadata: &[u8]) -> Result<(), MerkleError> {
+        Ok(())
+    }
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(None)
+    }
+    /// writes the nodes and metadata of a transaction, (see `MerkleTree::transaction`)
+    /// persistent stores override this to write them in one atomic batch,
+    /// so a crash can't leave a tree with only some of its new nodes
+    fn try_commit(&mut self, writes: StagedWrites<K>) -> Result<(), MerkleError>
+    where
+        Self: Sized,
+        K: Copy + Eq + std::hash::Hash,
+    {
+        writes.apply(self)
+    }
+    /// change the direction of the current root from (level, Center, 0) to (level, left, 0)
+    #[deprecated(note = "the tree shifts its own root when it grows, see `MerkleTree::try_append`")]
+    fn shift_root_to_left(&mut self, lowest_level: isize)
+    where
+        Self: NodeStore<PathTrace>,
+    {
+        try_shift_root_to_left(self, lowest_level).expect("failed to shift the root")
+    }
     fn unique_leaf_count(&self) -> usize {
         self.entries()
             .filter(|pairs| pairs.1.is_leaf)
@@ -53,6 +110,26 @@ pub trait NodeStore {
             .count()
     }
 }
+/// A key of the nodes in a persistent store, (sled, RocksDB and fjall).
+/// Next to the nodes these stores keep the key of every node by its hash for `NodeStore::get_key_by_hash`,
+/// unless the trees using the key never look a node up by its hash
+pub trait StoreKey: Copy {
+    const HASH_LOOKUP: bool = true;
+}
+impl StoreKey for PathTrace {}
+/// change the direction of the root at `lowest_level` from (level, Center, 0) to (level, left, 0)
+pub(crate) fn try_shift_root_to_left<S: NodeStore + ?Sized>(
+    store: &mut S,
+    lowest_level: isize,
+) -> Result<(), MerkleError> {
+    let mut root_path = PathTrace::root(lowest_level);
+    if let Some(root_node) = store.try_get(&root_path)? {
+        store.try_remove_node(root_path)?;
+        root_path.direction = HashDirection::Left;
+        store.try_set(root_path, root_node)?;
+    }
+    Ok(())
+}
 pub fn create_bytes_stream(size: usize) -> impl Iterator<Item = [u8; 8]> {
     (0..size).map(|num| num.to_be_bytes())
 }
@@ -61,25 +138,25 @@ pub fn create_bytes_stream(size: usize) -> impl Iterator<Item = [u8; 8]> {
 /// Our level index ordering is reversed for ease of use and lookup, so our root is at level 0, and the leaves are at the highest level
 pub type TreeCache = IndexMap<PathTrace, Node>;
 
-impl NodeStore for TreeCache {
+impl<K: Copy + Eq + std::hash::Hash + Ord> NodeStore<K> for IndexMap<K, Node> {
     fn trigger_batch_actions(&mut self) {
         // do nothing as this is not supported for this store
     }
     fn reserve(&mut self, items: usize) {
         self.reserve(items);
     }
-    fn exists(&self, key: &PathTrace) -> bool {
+    fn exists(&self, key: &K) -> bool {
         self.contains_key(key)
     }
-    fn set(&mut self, key: PathTrace, value: Node) -> Option<Node> {
+    fn set(&mut self, key: K, value: Node) -> Option<Node> {
         self.insert(key, value)
     }
 
-    fn get(&self, key: &PathTrace) -> Option<Node> {
+    fn get(&self, key: &K) -> Option<Node> {
         self.get(key).cloned()
     }
 
-    fn get_key_by_hash(&self, target_hash: &Hash) -> Option<PathTrace> {
+    fn get_key_by_hash(&self, target_hash: &Hash) -> Option<K> {
         self.binary_search_by(|_, node| node.data.cmp(target_hash))
             .ok()
             .map(|index| self.get_index(index).map(|(key, _)| *key))?
@@ -89,16 +166,16 @@ impl NodeStore for TreeCache {
             node1.data.cmp(&node2.data).then_with(|| path2.cmp(path))
         });
     }
-    fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)> {
+    fn entries(&self) -> impl Iterator<Item = (K, Node)> {
         self.iter().map(|(k, v)| (*k, *v))
     }
 
-    fn update_value(&mut self, key: &PathTrace, next_value: Node) {
+    fn update_value(&mut self, key: &K, next_value: Node) {
         if let Some(current) = self.get_mut(key) {
             *current = next_value
         }
     }
-    fn remove_node(&mut self, key: PathTrace) {
+    fn remove_node(&mut self, key: K) {
         let _ = self.shift_remove(&key);
     }
 }
@@ -1,69 +1,73 @@
-use super::NodeStore;
-use crate::{Node, PathTrace};
+use super::{NodeStore, StagedWrites, StoreKey};
+use crate::{Hash, MerkleError, Node, PathTrace};
 use rocksdb::{
     BoundColumnFamily, DBWithThreadMode, Error, IteratorMode, MultiThreaded, Options, ReadOptions,
     WriteBatch, WriteOptions,
 };
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
 pub type RocksDb = DBWithThreadMode<MultiThreaded>;
+const METADATA_KEY: &[u8] = b"tree";
+/// nodes keyed by `K` in a RocksDB column family, (see `NodeStore`)
 #[derive(Clone)]
-pub struct RocksDbStore<'a> {
+pub struct RocksDbStore<'a, K = PathTrace> {
     pub db: &'a RocksDb,
     cf_node_store: Arc<BoundColumnFamily<'a>>,
     cf_hash_key_store: Arc<BoundColumnFamily<'a>>,
+    cf_meta_store: Arc<BoundColumnFamily<'a>>,
     node_store_batch: Arc<Mutex<WriteBatch>>,
     hash_key_tree_batch: Arc<Mutex<WriteBatch>>,
+    key: PhantomData<K>,
 }
 impl<'a> RocksDbStore<'a> {
     pub fn new(db: &'a RocksDb, name_space: &str) -> Result<Self, Error> {
+        Self::new_keyed(db, name_space)
+    }
+}
+impl<'a, K> RocksDbStore<'a, K> {
+    /// a store for nodes keyed by `K`, eg. `RocksDbStore::<SparsePath>::new_keyed(&db, "sparse")`
+    pub fn new_keyed(db: &'a RocksDb, name_space: &str) -> Result<Self, Error> {
         let node_store_name = name_space;
         let lookup_store = format!("{node_store_name}-lookup");
-        db.create_cf(node_store_name, &Options::default())?;
-        db.create_cf(&lookup_store, &Options::default())?;
+        let meta_store = format!("{node_store_name}-meta");
+        // reuse existing column families, so persisted trees can be reopened
+        for name in [node_store_name, &lookup_store, &meta_store] {
+            if db.cf_handle(name).is_none() {
+                db.create_cf(name, &Options::default())?;
+            }
+        }
         let cf_node_store = db.cf_handle(node_store_name).expect("failed to get handle");
         let cf_hash_key_store = db.cf_handle(&lookup_store).expect("failed to get handle");
+        let cf_meta_store = db.cf_handle(&meta_store).expect("failed to get handle");
 
         Ok(Self {
             db,
             cf_node_store,
             cf_hash_key_store,
+            cf_meta_store,
             node_store_batch: Arc::default(),
             hash_key_tree_batch: Arc::default(),
+            key: PhantomData,
         })
     }
 }
 
-impl NodeStore for RocksDbStore<'_> {
+impl<K: StoreKey + Serialize + DeserializeOwned> NodeStore<K> for RocksDbStore<'_, K> {
     fn store_type(&self) -> super::StoreType {
         super::StoreType::RocksDb
     }
-    fn set(&mut self, key: PathTrace, value: Node) -> Option<Node> {
-        let path: Vec<u8> = bincode::serialize(&key).ok()?;
-        let node: Vec<u8> = bincode::serialize(&value).ok()?;
-        let hash: Vec<u8> = bincode::serialize(&value.data).ok()?;
-        let mut node_store_batch = self.node_store_batch.lock().unwrap();
-        node_store_batch.put_cf(&self.cf_node_store, &path, node);
-        // skip updating this for duplicates
-        if !self.db.key_may_exist_cf(&self.cf_hash_key_store, &hash) {
-            let _ = self.db.put_cf(&self.cf_hash_key_store, hash, path);
-        }
-        Some(value)
+    fn set(&mut self, key: K, value: Node) -> Option<Node> {
+        self.try_set(key, value)
+            .expect("rocksdb failed to set the node")
     }
 
-    fn get(&self, key: &PathTrace) -> Option<Node> {
-        let path: Vec<u8> = bincode::serialize(&key).ok()?;
-        self.db
-            .get_cf(&self.cf_node_store, &path)
-            .ok()?
-            .and_then(|v| bincode::deserialize(&v).ok())
+    fn get(&self, key: &K) -> Option<Node> {
+        self.try_get(key).ok().flatten()
     }
 
-    fn get_key_by_hash(&self, hash: &crate::Hash) -> Option<PathTrace> {
-        if let Some(path_bytes) = self.db.get_cf(&self.cf_hash_key_store, hash).ok()? {
-            return bincode::deserialize(&path_bytes).ok();
-        }
-
-        None
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<K> {
+        self.try_get_key_by_hash(hash).ok().flatten()
     }
 
     fn sort(&mut self) {
@@ -71,21 +75,20 @@ impl NodeStore for RocksDbStore<'_> {
         // only needed for a  binary_search_hash
     }
 
-    fn exists(&self, key: &PathTrace) -> bool {
-        let key: Vec<u8> = bincode::serialize(&key).unwrap();
-        self.db.key_may_exist_cf(&self.cf_node_store, &key)
+    fn exists(&self, key: &K) -> bool {
+        self.try_exists(key).unwrap_or_default()
     }
 
     fn reserve(&mut self, _items: usize) {
         // not required
     }
 
-    fn update_value(&mut self, key: &PathTrace, next_value: Node) {
+    fn update_value(&mut self, key: &K, next_value: Node) {
         self.set(*key, next_value);
         self.trigger_batch_actions();
     }
 
-    fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)> {
+    fn entries(&self) -> impl Iterator<Item = (K, Node)> {
         let mut opts = ReadOptions::default();
         opts.fill_cache(false);
         let mut raw = self.db.raw_iterator_cf_opt(&self.cf_node_store, opts);
@@ -94,7 +97,7 @@ impl NodeStore for RocksDbStore<'_> {
             if raw.valid() {
                 let key = raw.key().unwrap();
                 let val = raw.value().unwrap();
-                let path: PathTrace = bincode::deserialize(key).unwrap();
+                let path: K = bincode::deserialize(key).unwrap();
                 let node: Node = bincode::deserialize(val).unwrap();
                 raw.next();
                 Some((path, node))
@@ -107,27 +110,149 @@ impl NodeStore for RocksDbStore<'_> {
     }
 
     fn trigger_batch_actions(&mut self) {
+        self.try_trigger_batch_actions()
+            .expect("rocksdb failed to write the batch")
+    }
+
+    fn remove_node(&mut self, key: K) {
+        self.try_remove_node(key)
+            .expect("rocksdb failed to remove the node")
+    }
+
+    fn try_set(&mut self, key: K, value: Node) -> Result<Option<Node>, MerkleError> {
+        let path: Vec<u8> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        let node: Vec<u8> = bincode::serialize(&value).map_err(MerkleError::serialization)?;
+        let hash: Vec<u8> = bincode::serialize(&value.data).map_err(MerkleError::serialization)?;
+        let mut node_store_batch = self
+            .node_store_batch
+            .lock()
+            .map_err(|_| MerkleError::store("the node batch lock is poisoned"))?;
+        node_store_batch.put_cf(&self.cf_node_store, &path, node);
+        // skip updating this for duplicates
+        if K::HASH_LOOKUP && !self.db.key_may_exist_cf(&self.cf_hash_key_store, &hash) {
+            self.db
+                .put_cf(&self.cf_hash_key_store, hash, path)
+                .map_err(MerkleError::store)?;
+        }
+        Ok(Some(value))
+    }
+
+    fn try_get(&self, key: &K) -> Result<Option<Node>, MerkleError> {
+        let path: Vec<u8> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        self.db
+            .get_cf(&self.cf_node_store, &path)
+            .map_err(MerkleError::store)?
+            .map(|v| bincode::deserialize(&v).map_err(MerkleError::serialization))
+            .transpose()
+    }
+
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<K>, MerkleError> {
+        self.db
+            .get_cf(&self.cf_hash_key_store, hash)
+            .map_err(MerkleError::store)?
+            .map(|path_bytes| bincode::deserialize(&path_bytes).map_err(MerkleError::serialization))
+            .transpose()
+    }
+
+    fn try_exists(&self, key: &K) -> Result<bool, MerkleError> {
+        let key: Vec<u8> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        Ok(self.db.key_may_exist_cf(&self.cf_node_store, &key))
+    }
+
+    fn try_update_value(&mut self, key: &K, next_value: Node) -> Result<(), MerkleError> {
+        self.try_set(*key, next_value)?;
+        self.try_trigger_batch_actions()
+    }
+
+    fn try_trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
         let mut opts = WriteOptions::default();
         opts.set_sync(false);
         opts.disable_wal(true);
-        let mut node_store_batch = self.node_store_batch.lock().unwrap();
-        let mut hash_key_tree_batch = self.hash_key_tree_batch.lock().unwrap();
-        let node_store_batch = std::mem::take(&mut *node_store_batch);
-        let hash_key_tree_batch = std::mem::take(&mut *hash_key_tree_batch);
-        let _ = self.db.write_opt(node_store_batch, &opts);
-        let _ = self.db.write_opt(hash_key_tree_batch, &opts);
-    }
-
-    fn remove_node(&mut self, key: PathTrace) {
-        if let Ok(key_v) = bincode::serialize(&key) {
-            if let Some(node) = self.get(&key) {
-                if self.db.delete_cf(&self.cf_node_store, key_v).is_ok() {
-                    //remove it from hash_key_store
-                    let hash = node.data;
-                    let _ = self.db.delete_cf(&self.cf_hash_key_store, hash);
-                }
+        let poisoned = |_| MerkleError::store("the node batch lock is poisoned");
+        let node_store_batch =
+            std::mem::take(&mut *self.node_store_batch.lock().map_err(poisoned)?);
+        let hash_key_tree_batch =
+            std::mem::take(&mut *self.hash_key_tree_batch.lock().map_err(poisoned)?);
+        self.db
+            .write_opt(node_store_batch, &opts)
+            .map_err(MerkleError::store)?;
+        self.db
+            .write_opt(hash_key_tree_batch, &opts)
+            .map_err(MerkleError::store)
+    }
+
+    fn try_remove_node(&mut self, key: K) -> Result<(), MerkleError> {
+        let key_v = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        if let Some(node) = self.try_get(&key)? {
+            self.db
+                .delete_cf(&self.cf_node_store, &key_v)
+                .map_err(MerkleError::store)?;
+            if !K::HASH_LOOKUP {
+                return Ok(());
+            }
+            //remove it from hash_key_store, unless it points at another node with the same hash
+            let lookup = self
+                .db
+                .get_cf(&self.cf_hash_key_store, node.data)
+                .map_err(MerkleError::store)?;
+            if lookup.is_some_and(|path| path == key_v) {
+                self.db
+                    .delete_cf(&self.cf_hash_key_store, node.data)
+                    .map_err(MerkleError::store)?;
             }
         }
+        Ok(())
+    }
+
+    fn save_metadata(&mut self, metadata: &[u8]) -> Result<(), MerkleError> {
+        self.db
+            .put_cf(&self.cf_meta_store, METADATA_KEY, metadata)
+            .map_err(MerkleError::store)
+    }
+
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        self.db
+            .get_cf(&self.cf_meta_store, METADATA_KEY)
+            .map_err(MerkleError::store)
+    }
+
+    /// commits the writes in one write batch over the nodes, lookups and metadata
+    fn try_commit(&mut self, writes: StagedWrites<K>) -> Result<(), MerkleError>
+    where
+        K: Copy + Eq + std::hash::Hash,
+    {
+        self.try_trigger_batch_actions()?;
+        let raw = writes.into_raw(
+            |key| {
+                self.db
+                    .get_cf(&self.cf_node_store, key)
+                    .map_err(MerkleError::store)?
+                    .map(|node| bincode::deserialize(&node).map_err(MerkleError::serialization))
+                    .transpose()
+            },
+            |hash| {
+                self.db
+                    .get_cf(&self.cf_hash_key_store, hash)
+                    .map_err(MerkleError::store)
+            },
+        )?;
+        let mut batch = WriteBatch::default();
+        for key in raw.removed_nodes {
+            batch.delete_cf(&self.cf_node_store, key);
+        }
+        for (key, node) in raw.nodes {
+            batch.put_cf(&self.cf_node_store, key, node);
+        }
+        for hash in raw.removed_lookups {
+            batch.delete_cf(&self.cf_hash_key_store, hash);
+        }
+        for (hash, key) in raw.lookups {
+            batch.put_cf(&self.cf_hash_key_store, hash, key);
+        }
+        if let Some(metadata) = raw.metadata {
+            batch.put_cf(&self.cf_meta_store, METADATA_KEY, metadata);
+        }
+        self.db.write(batch).map_err(MerkleError::store)
     }
 }
 
@@ -1,81 +1,87 @@
-use super::NodeStore;
+use super::{NodeStore, StagedWrites, StoreKey};
 use crate::Node;
 use crate::PathTrace;
-use sled::{Batch, Config, Db, Mode, Result};
+use crate::{Hash, MerkleError};
+use serde::{de::DeserializeOwned, Serialize};
+use sled::transaction::{ConflictableTransactionError, Transactional};
+use sled::{Batch, Config, Db, Mode};
 use sled::{IVec, Tree};
+use std::marker::PhantomData;
+const METADATA_KEY: &[u8] = b"tree";
+/// nodes keyed by `K` in a sled tree, (see `NodeStore`)
 #[derive(Clone, Debug)]
-pub struct SledStore {
+pub struct SledStore<K = PathTrace> {
     node_store: Tree,    // (path_trace, Node)
     hash_key_tree: Tree, // (hash, path_trace)
+    meta_tree: Tree,     // tree metadata, (see `TreeMetadata`)
     node_store_batch: Batch,
     hash_key_tree_batch: Batch,
+    key: PhantomData<K>,
 }
 
 impl SledStore {
-    pub fn new(db: &Db, name: &str) -> Result<Self> {
+    pub fn new(db: &Db, name: &str) -> sled::Result<Self> {
+        Self::new_keyed(db, name)
+    }
+}
+impl<K> SledStore<K> {
+    /// a store for nodes keyed by `K`, eg. `SledStore::<SparsePath>::new_keyed(&db, "sparse")`
+    pub fn new_keyed(db: &Db, name: &str) -> sled::Result<Self> {
         let node_store = db.open_tree(name)?;
         let hash_key_tree = db.open_tree(format!("{name}-lookup"))?;
+        let meta_tree = db.open_tree(format!("{name}-meta"))?;
         let node_store_batch = Batch::default();
         let hash_key_tree_batch = Batch::default();
         Ok(Self {
             node_store,
             hash_key_tree,
+            meta_tree,
             node_store_batch,
             hash_key_tree_batch,
+            key: PhantomData,
         })
     }
     pub fn get_node(&self, key: impl AsRef<[u8]>) -> Option<Node> {
-        let result = self.node_store.get(key).ok()?;
-        if let Some(node) = result {
-            let value = bincode::deserialize(&node).ok();
-            return value;
-        }
-        None
+        self.try_get_node(key).ok().flatten()
+    }
+    pub fn try_get_node(&self, key: impl AsRef<[u8]>) -> Result<Option<Node>, MerkleError> {
+        self.node_store
+            .get(key)
+            .map_err(MerkleError::store)?
+            .map(|node| bincode::deserialize(&node).map_err(MerkleError::serialization))
+            .transpose()
     }
 }
-impl NodeStore for SledStore {
+impl<K: StoreKey + Serialize + DeserializeOwned> NodeStore<K> for SledStore<K> {
     fn store_type(&self) -> super::StoreType {
         super::StoreType::Sled
     }
-    fn set(&mut self, key: crate::PathTrace, value: crate::Node) -> Option<crate::Node> {
-        let path: IVec = bincode::serialize(&key).ok()?.into();
-        let node: IVec = bincode::serialize(&value).ok()?.into();
-        let hash = value.data;
-        let _ = self.node_store.insert(&path, node);
-        // skip updating this for duplicates
-        if !self.hash_key_tree.contains_key(hash).unwrap_or_default() {
-            let _ = self.hash_key_tree.insert(hash, path);
-        }
-        Some(value)
+    fn set(&mut self, key: K, value: Node) -> Option<Node> {
+        self.try_set(key, value)
+            .expect("sled failed to set the node")
     }
 
-    fn get(&self, key: &crate::PathTrace) -> Option<crate::Node> {
-        let key: Vec<_> = bincode::serialize(&key).ok()?;
-        self.get_node(key)
+    fn get(&self, key: &K) -> Option<Node> {
+        self.try_get(key).ok().flatten()
     }
 
-    fn get_key_by_hash(&self, hash: &crate::Hash) -> Option<crate::PathTrace> {
-        if let Some(path_bytes) = self.hash_key_tree.get(hash).ok()? {
-            return bincode::deserialize(&path_bytes).ok();
-        }
-
-        None
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<K> {
+        self.try_get_key_by_hash(hash).ok().flatten()
     }
 
     fn sort(&mut self) {
         // do nothing, as can't binary_search by hash_value
     }
 
-    fn exists(&self, key: &crate::PathTrace) -> bool {
-        let key: Vec<_> = bincode::serialize(&key).ok().unwrap_or_default();
-        self.node_store.contains_key(key).unwrap_or_default()
+    fn exists(&self, key: &K) -> bool {
+        self.try_exists(key).unwrap_or_default()
     }
 
     fn reserve(&mut self, _items: usize) {
         // not supported by sled, do nothing
     }
 
-    fn entries(&self) -> impl Iterator<Item = (crate::PathTrace, crate::Node)> {
+    fn entries(&self) -> impl Iterator<Item = (K, Node)> {
         self.node_store.iter().flat_map(|entry| {
             if let Ok((key, value)) = entry {
                 if let (Ok(path), Ok(node)) = (
@@ -90,35 +96,152 @@ impl NodeStore for SledStore {
         })
     }
 
-    fn update_value(&mut self, key: &PathTrace, next_value: Node) {
+    fn update_value(&mut self, key: &K, next_value: Node) {
         self.set(*key, next_value);
         self.trigger_batch_actions();
     }
 
     fn trigger_batch_actions(&mut self) {
+        self.try_trigger_batch_actions()
+            .expect("sled failed to write the batch")
+    }
+
+    fn remove_node(&mut self, key: K) {
+        self.try_remove_node(key)
+            .expect("sled failed to remove the node")
+    }
+
+    fn try_set(&mut self, key: K, value: Node) -> Result<Option<Node>, MerkleError> {
+        let path: IVec = bincode::serialize(&key)
+            .map_err(MerkleError::serialization)?
+            .into();
+        let node: IVec = bincode::serialize(&value)
+            .map_err(MerkleError::serialization)?
+            .into();
+        let hash = value.data;
+        self.node_store
+            .insert(&path, node)
+            .map_err(MerkleError::store)?;
+        // skip updating this for duplicates
+        if K::HASH_LOOKUP
+            && !self
+                .hash_key_tree
+                .contains_key(hash)
+                .map_err(MerkleError::store)?
+        {
+            self.hash_key_tree
+                .insert(hash, path)
+                .map_err(MerkleError::store)?;
+        }
+        Ok(Some(value))
+    }
+
+    fn try_get(&self, key: &K) -> Result<Option<Node>, MerkleError> {
+        let key: Vec<_> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        self.try_get_node(key)
+    }
+
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<K>, MerkleError> {
+        self.hash_key_tree
+            .get(hash)
+            .map_err(MerkleError::store)?
+            .map(|path_bytes| bincode::deserialize(&path_bytes).map_err(MerkleError::serialization))
+            .transpose()
+    }
+
+    fn try_exists(&self, key: &K) -> Result<bool, MerkleError> {
+        let key: Vec<_> = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        self.node_store
+            .contains_key(key)
+            .map_err(MerkleError::store)
+    }
+
+    fn try_update_value(&mut self, key: &K, next_value: Node) -> Result<(), MerkleError> {
+        self.try_set(*key, next_value)?;
+        self.try_trigger_batch_actions()
+    }
+
+    fn try_trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
         let node_store_batch = std::mem::take(&mut self.node_store_batch);
         let hash_key_tree_batch = std::mem::take(&mut self.hash_key_tree_batch);
         self.node_store
             .apply_batch(node_store_batch)
-            .expect("failed to insert");
+            .map_err(MerkleError::store)?;
         self.hash_key_tree
             .apply_batch(hash_key_tree_batch)
-            .expect("failed to insert")
-    }
-
-    fn remove_node(&mut self, key: PathTrace) {
-        if let Ok(key) = bincode::serialize(&key) {
-            if let Some(node) = self
-                .node_store
-                .remove(key)
-                .ok()
-                .flatten()
-                .and_then(|value| bincode::deserialize::<Node>(value.as_ref()).ok())
-            {
-                // remove it from the hash_key_tree;
-                let _ = self.hash_key_tree.remove(node.data);
+            .map_err(MerkleError::store)
+    }
+
+    fn try_remove_node(&mut self, key: K) -> Result<(), MerkleError> {
+        let key = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+        if let Some(value) = self.node_store.remove(&key).map_err(MerkleError::store)? {
+            if !K::HASH_LOOKUP {
+                return Ok(());
+            }
+            let node: Node =
+                bincode::deserialize(value.as_ref()).map_err(MerkleError::serialization)?;
+            // remove it from the hash_key_tree, unless it points at another node with the same hash
+            let lookup = self
+                .hash_key_tree
+                .get(node.data)
+                .map_err(MerkleError::store)?;
+            if lookup.is_some_and(|path| path.as_ref() == key.as_slice()) {
+                self.hash_key_tree
+                    .remove(node.data)
+                    .map_err(MerkleError::store)?;
             }
         }
+        Ok(())
+    }
+
+    fn save_metadata(&mut self, metadata: &[u8]) -> Result<(), MerkleError> {
+        self.meta_tree
+            .insert(METADATA_KEY, metadata)
+            .map_err(MerkleError::store)?;
+        Ok(())
+    }
+
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self
+            .meta_tree
+            .get(METADATA_KEY)
+            .map_err(MerkleError::store)?
+            .map(|bytes| bytes.to_vec()))
+    }
+
+    /// commits the writes in one transaction over the nodes, lookups and metadata
+    fn try_commit(&mut self, writes: StagedWrites<K>) -> Result<(), MerkleError>
+    where
+        K: Copy + Eq + std::hash::Hash,
+    {
+        self.try_trigger_batch_actions()?;
+        let raw = writes.into_raw(
+            |key| self.try_get_node(key),
+            |hash| {
+                let lookup = self.hash_key_tree.get(hash).map_err(MerkleError::store)?;
+                Ok(lookup.map(|path| path.to_vec()))
+            },
+        )?;
+        (&self.node_store, &self.hash_key_tree, &self.meta_tree)
+            .transaction(|(nodes, lookups, meta)| {
+                for key in &raw.removed_nodes {
+                    nodes.remove(key.as_slice())?;
+                }
+                for (key, node) in &raw.nodes {
+                    nodes.insert(key.as_slice(), node.as_slice())?;
+                }
+                for hash in &raw.removed_lookups {
+                    lookups.remove(hash.as_slice())?;
+                }
+                for (hash, key) in &raw.lookups {
+                    lookups.insert(hash.as_slice(), key.as_slice())?;
+                }
+                if let Some(metadata) = &raw.metadata {
+                    meta.insert(METADATA_KEY, metadata.as_slice())?;
+                }
+                Ok::<_, ConflictableTransactionError>(())
+            })
+            .map_err(MerkleError::store)
     }
 }
 
@@ -0,0 +1,262 @@
+#[cfg(any(
+    feature = "sled",
+    feature = "all-stores",
+    feature = "rocksdb",
+    feature = "fjall"
+))]
+use super::StoreKey;
+use super::{NodeStore, StoreType};
+use crate::{Hash, MerkleError, Node, PathTrace};
+use indexmap::IndexMap;
+#[cfg(any(
+    feature = "sled",
+    feature = "all-stores",
+    feature = "rocksdb",
+    feature = "fjall"
+))]
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Keeps the writes of a tree over `base` in memory instead of applying them,
+/// reads see the staged nodes first. `base` is only read, so others can keep reading it
+/// until the writes are applied at once with `StagedWrites::apply`
+#[derive(Debug)]
+pub struct StagedStore<'a, S, K = PathTrace> {
+    base: &'a S,
+    writes: StagedWrites<K>,
+    /// the keys of the staged nodes by hash
+    lookup: HashMap<Hash, K>,
+}
+
+/// The node writes of a `StagedStore`
+#[derive(Debug, Clone, Default)]
+pub struct StagedWrites<K = PathTrace> {
+    /// the staged nodes in the order they were written, `None` for removed nodes
+    pub nodes: IndexMap<K, Option<Node>>,
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl<'a, S: NodeStore<K>, K: Copy + Eq + std::hash::Hash> StagedStore<'a, S, K> {
+    pub fn new(base: &'a S) -> Self {
+        Self {
+            base,
+            writes: StagedWrites {
+                nodes: IndexMap::new(),
+                metadata: None,
+            },
+            lookup: HashMap::new(),
+        }
+    }
+    pub fn into_writes(self) -> StagedWrites<K> {
+        self.writes
+    }
+}
+
+impl<K: Copy + Eq + std::hash::Hash> StagedWrites<K> {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.metadata.is_none()
+    }
+    /// writes the staged nodes and metadata to `store`, triggering its batch once.
+    /// Replaced nodes are removed first, so the store drops the lookups of their old hashes
+    pub fn apply<S: NodeStore<K>>(self, store: &mut S) -> Result<(), MerkleError> {
+        // removals aren't batched by every store, so they go before the batched sets
+        let mut sets = Vec::with_capacity(self.nodes.len());
+        for (key, node) in self.nodes {
+            let old = store.try_get(&key)?;
+            match node {
+                Some(node) if old.is_some_and(|old| old.data == node.data) => {
+                    sets.push((key, node))
+                }
+                Some(node) => {
+                    if old.is_some() {
+                        store.try_remove_node(key)?;
+                    }
+                    sets.push((key, node));
+                }
+                None if old.is_some() => store.try_remove_node(key)?,
+                None => {}
+            }
+        }
+        store.reserve(sets.len());
+        for (key, node) in sets {
+            store.try_set(key, node)?;
+        }
+        store.try_trigger_batch_actions()?;
+        if let Some(metadata) = self.metadata {
+            store.save_metadata(&metadata)?;
+        }
+        store.sort();
+        Ok(())
+    }
+}
+
+/// `StagedWrites` as the operations on a store keeping serialized nodes by serialized key
+/// and the key of a node by its hash, (see `NodeStore::try_commit`)
+/// to be written in this order in one atomic batch
+#[cfg(any(
+    feature = "sled",
+    feature = "all-stores",
+    feature = "rocksdb",
+    feature = "fjall"
+))]
+#[derive(Debug, Default)]
+pub(crate) struct RawWrites {
+    pub removed_nodes: Vec<Vec<u8>>,
+    pub nodes: Vec<(Vec<u8>, Vec<u8>)>,
+    pub removed_lookups: Vec<Hash>,
+    pub lookups: Vec<(Hash, Vec<u8>)>,
+    pub metadata: Option<Vec<u8>>,
+}
+
+#[cfg(any(
+    feature = "sled",
+    feature = "all-stores",
+    feature = "rocksdb",
+    feature = "fjall"
+))]
+impl<K: StoreKey + Eq + std::hash::Hash + Serialize> StagedWrites<K> {
+    /// the operations `apply` would do on a store with the given node and lookup reads,
+    /// removing replaced nodes and their lookups before setting the new ones.
+    /// Lookups that don't point at a node with their hash, (eg. after a crash) are replaced,
+    /// no lookups are written for keys without them, (see `StoreKey`)
+    pub(crate) fn into_raw(
+        self,
+        node: impl Fn(&[u8]) -> Result<Option<Node>, MerkleError>,
+        lookup: impl Fn(&Hash) -> Result<Option<Vec<u8>>, MerkleError>,
+    ) -> Result<RawWrites, MerkleError> {
+        let mut raw = RawWrites {
+            metadata: self.metadata,
+            ..RawWrites::default()
+        };
+        let staged = self
+            .nodes
+            .into_iter()
+            .map(|(key, next)| {
+                let key = bincode::serialize(&key).map_err(MerkleError::serialization)?;
+                Ok((key, next))
+            })
+            .collect::<Result<IndexMap<_, _>, MerkleError>>()?;
+        // the node at a key once the writes are committed
+        let committed = |key: &Vec<u8>| match staged.get(key) {
+            Some(next) => Ok(*next),
+            None => node(key),
+        };
+        // the lookups as changed by the earlier operations
+        let mut lookups: HashMap<Hash, Option<Vec<u8>>> = HashMap::new();
+        for (key, next) in &staged {
+            let Some(old) = node(key)? else {
+                continue;
+            };
+            if next.is_none_or(|next| next.data != old.data) {
+                raw.removed_nodes.push(key.clone());
+                if !K::HASH_LOOKUP {
+                    continue;
+                }
+                let current = match lookups.get(&old.data) {
+                    Some(current) => current.clone(),
+                    None => lookup(&old.data)?,
+                };
+                if current.as_ref() == Some(key) {
+                    raw.removed_lookups.push(old.data);
+                    lookups.insert(old.data, None);
+                }
+            }
+        }
+        for (key, next) in &staged {
+            let Some(next) = next else {
+                continue;
+            };
+            let node = bincode::serialize(next).map_err(MerkleError::serialization)?;
+            raw.nodes.push((key.clone(), node));
+            if !K::HASH_LOOKUP {
+                continue;
+            }
+            let current = match lookups.get(&next.data) {
+                Some(current) => current.clone(),
+                None => lookup(&next.data)?,
+            };
+            // keep the lookup of other nodes with the same hash
+            let found = match &current {
+                Some(current) => committed(current)?.is_some_and(|found| found.data == next.data),
+                None => false,
+            };
+            if !found {
+                raw.lookups.push((next.data, key.clone()));
+                lookups.insert(next.data, Some(key.clone()));
+            }
+        }
+        Ok(raw)
+    }
+}
+
+impl<S: NodeStore<K>, K: Copy + Eq + std::hash::Hash> NodeStore<K> for StagedStore<'_, S, K> {
+    fn store_type(&self) -> StoreType {
+        self.base.store_type()
+    }
+    fn set(&mut self, key: K, value: Node) -> Option<Node> {
+        self.try_set(key, value)
+            .expect("failed to read the base store")
+    }
+    fn get(&self, key: &K) -> Option<Node> {
+        self.try_get(key).ok().flatten()
+    }
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<K> {
+        self.try_get_key_by_hash(hash).ok().flatten()
+    }
+    // the base store is sorted when the writes are applied
+    fn sort(&mut self) {}
+    fn exists(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+    fn reserve(&mut self, items: usize) {
+        self.writes.nodes.reserve(items);
+    }
+    fn update_value(&mut self, key: &K, next_value: Node) {
+        if self.exists(key) {
+            self.set(*key, next_value);
+        }
+    }
+    fn entries(&self) -> impl Iterator<Item = (K, Node)> {
+        let nodes = &self.writes.nodes;
+        let base = self
+            .base
+            .entries()
+            .filter(|(key, _)| !nodes.contains_key(key));
+        let staged = nodes
+            .iter()
+            .filter_map(|(key, node)| node.map(|node| (*key, node)));
+        base.chain(staged)
+    }
+    fn trigger_batch_actions(&mut self) {}
+    fn remove_node(&mut self, key: K) {
+        self.writes.nodes.insert(key, None);
+    }
+    fn try_set(&mut self, key: K, value: Node) -> Result<Option<Node>, MerkleError> {
+        let old = self.try_get(&key)?;
+        self.writes.nodes.insert(key, Some(value));
+        self.lookup.insert(value.data, key);
+        Ok(old)
+    }
+    fn try_get(&self, key: &K) -> Result<Option<Node>, MerkleError> {
+        match self.writes.nodes.get(key) {
+            Some(node) => Ok(*node),
+            None => self.base.try_get(key),
+        }
+    }
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<K>, MerkleError> {
+        match self.lookup.get(hash) {
+            Some(key) => Ok(Some(*key)),
+            None => self.base.try_get_key_by_hash(hash),
+        }
+    }
+    fn save_metadata(&mut self, metadata: &[u8]) -> Result<(), MerkleError> {
+        self.writes.metadata = Some(metadata.to_vec());
+        Ok(())
+    }
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        match &self.writes.metadata {
+            Some(metadata) => Ok(Some(metadata.clone())),
+            None => self.base.load_metadata(),
+        }
+    }
+}
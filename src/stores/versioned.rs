@@ -0,0 +1,363 @@
+use super::{NodeStore, StoreKey, StoreType};
+use crate::{Hash, MerkleError, Node, PathTrace};
+#[cfg(any(
+    feature = "sled",
+    feature = "all-stores",
+    feature = "rocksdb",
+    feature = "fjall"
+))]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Key of a node version in the store under a `VersionedStore`,
+/// the node written at `path` in `version`, or its removal
+#[cfg_attr(
+    any(
+        feature = "sled",
+        feature = "rocksdb",
+        feature = "all-stores",
+        feature = "fjall"
+    ),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, std::hash::Hash)]
+pub struct VersionedPath {
+    pub path: PathTrace,
+    pub version: u64,
+    /// marks the removal of the node at `path` in `version`, stored with a default node
+    pub removed: bool,
+}
+
+// a `VersionedStore` keeps the lookup of the latest nodes itself
+impl StoreKey for VersionedPath {
+    const HASH_LOOKUP: bool = false;
+}
+
+/// How many versions a `VersionedStore` keeps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VersionRetention {
+    #[default]
+    All,
+    /// the latest versions, older ones are pruned whenever a version is completed
+    Last(usize),
+}
+
+/// Copy-on-write node versions over a store keyed by `VersionedPath`, so a tree over it can
+/// still prove against the roots it had before, (see `MerkleTree::prove_at`).
+/// Every saved tree metadata completes a version, (one per construction, append, update etc.)
+/// the nodes written since are stored as new versions instead of overwriting the old ones.
+/// Which versions hold a node at each path is kept in memory and rebuilt from the store on `new`
+#[derive(Debug)]
+pub struct VersionedStore<S> {
+    store: S,
+    /// the versions written at each path in ascending order, `true` for removals
+    index: HashMap<PathTrace, Vec<(u64, bool)>>,
+    /// the path of the latest node with each hash
+    lookup: HashMap<Hash, PathTrace>,
+    /// the saved metadata of every completed version that wasn't pruned
+    versions: Vec<(u64, Vec<u8>)>,
+    /// the version being written
+    version: u64,
+    retention: VersionRetention,
+}
+
+impl<S: NodeStore<VersionedPath>> VersionedStore<S> {
+    const FORMAT: u8 = 1;
+
+    /// the versions already in `store`, if any
+    pub fn new(store: S) -> Result<Self, MerkleError> {
+        Self::with_retention(store, VersionRetention::All)
+    }
+    pub fn with_retention(store: S, retention: VersionRetention) -> Result<Self, MerkleError> {
+        let versions = match store.load_metadata()? {
+            Some(bytes) => Self::decode_versions(&bytes)?,
+            None => vec![],
+        };
+        let mut index: HashMap<PathTrace, Vec<(u64, bool)>> = HashMap::new();
+        for (key, _) in store.entries() {
+            index
+                .entry(key.path)
+                .or_default()
+                .push((key.version, key.removed));
+        }
+        index.values_mut().for_each(|versions| versions.sort());
+        let mut versioned = Self {
+            store,
+            index,
+            lookup: HashMap::new(),
+            version: versions.last().map_or(0, |(version, _)| version + 1),
+            versions,
+            retention,
+        };
+        for path in versioned.index.keys() {
+            if let Some(node) = versioned.try_get_at(path, u64::MAX)? {
+                versioned.lookup.insert(node.data, *path);
+            }
+        }
+        Ok(versioned)
+    }
+    /// `FORMAT | count | (version | length | metadata)*`, integers as little endian
+    fn encode_versions(&self) -> Vec<u8> {
+        let mut bytes = vec![Self::FORMAT];
+        bytes.extend((self.versions.len() as u64).to_le_bytes());
+        for (version, metadata) in &self.versions {
+            bytes.extend(version.to_le_bytes());
+            bytes.extend((metadata.len() as u32).to_le_bytes());
+            bytes.extend(metadata);
+        }
+        bytes
+    }
+    fn decode_versions(bytes: &[u8]) -> Result<Vec<(u64, Vec<u8>)>, MerkleError> {
+        let invalid = || MerkleError::InvalidMetadata("corrupt version list".to_string());
+        let mut rest = match bytes.split_first() {
+            Some((&Self::FORMAT, rest)) => rest,
+            _ => return Err(invalid()),
+        };
+        let mut take = |count: usize| -> Result<&[u8], MerkleError> {
+            let (taken, remaining) = rest.split_at_checked(count).ok_or_else(invalid)?;
+            rest = remaining;
+            Ok(taken)
+        };
+        let to_u64 = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().expect("8 bytes"));
+        let count = to_u64(take(8)?);
+        let mut versions = vec![];
+        for _ in 0..count {
+            let version = to_u64(take(8)?);
+            let length = u32::from_le_bytes(take(4)?.try_into().expect("4 bytes"));
+            versions.push((version, take(length as usize)?.to_vec()));
+        }
+        Ok(versions)
+    }
+    /// the completed versions that weren't pruned, oldest first
+    pub fn versions(&self) -> impl Iterator<Item = u64> + '_ {
+        self.versions.iter().map(|(version, _)| *version)
+    }
+    /// the metadata saved when `version` was completed
+    pub fn metadata_at(&self, version: u64) -> Option<&[u8]> {
+        let position = self
+            .versions
+            .binary_search_by_key(&version, |(version, _)| *version)
+            .ok()?;
+        Some(&self.versions[position].1)
+    }
+    /// the node at `path` as it was when `version` was completed
+    pub fn try_get_at(&self, path: &PathTrace, version: u64) -> Result<Option<Node>, MerkleError> {
+        let Some(versions) = self.index.get(path) else {
+            return Ok(None);
+        };
+        let position = versions.partition_point(|(written, _)| *written <= version);
+        match position.checked_sub(1).map(|position| versions[position]) {
+            Some((written, false)) => self.store.try_get(&VersionedPath {
+                path: *path,
+                version: written,
+                removed: false,
+            }),
+            _ => Ok(None),
+        }
+    }
+    /// the nodes as they were when `version` was completed, (read-only)
+    pub fn at(&self, version: u64) -> VersionView<'_, S> {
+        VersionView {
+            store: self,
+            version,
+        }
+    }
+    /// drops every version before `version`, the nodes still visible in `version` are kept
+    pub fn prune_before(&mut self, version: u64) -> Result<(), MerkleError> {
+        let mut dropped = vec![];
+        for (path, versions) in self.index.iter_mut() {
+            let visible = versions.partition_point(|(written, _)| *written <= version);
+            // a removal only hides older versions
+            let keep_from = match visible.checked_sub(1) {
+                Some(last) if !versions[last].1 => last,
+                _ => visible,
+            };
+            for (written, removed) in versions.drain(..keep_from) {
+                dropped.push(VersionedPath {
+                    path: *path,
+                    version: written,
+                    removed,
+                });
+            }
+        }
+        self.index.retain(|_, versions| !versions.is_empty());
+        for key in dropped {
+            self.store.try_remove_node(key)?;
+        }
+        self.store.try_trigger_batch_actions()?;
+        self.versions.retain(|(completed, _)| *completed >= version);
+        let versions = self.encode_versions();
+        self.store.save_metadata(&versions)
+    }
+}
+
+impl<S: NodeStore<VersionedPath>> NodeStore<PathTrace> for VersionedStore<S> {
+    fn store_type(&self) -> StoreType {
+        self.store.store_type()
+    }
+    fn set(&mut self, key: PathTrace, value: Node) -> Option<Node> {
+        self.try_set(key, value)
+            .expect("failed to write a node version")
+    }
+    fn get(&self, key: &PathTrace) -> Option<Node> {
+        self.try_get(key).ok().flatten()
+    }
+    fn get_key_by_hash(&self, hash: &Hash) -> Option<PathTrace> {
+        self.lookup.get(hash).copied()
+    }
+    fn sort(&mut self) {
+        self.store.sort();
+    }
+    fn exists(&self, key: &PathTrace) -> bool {
+        self.get(key).is_some()
+    }
+    fn reserve(&mut self, items: usize) {
+        self.store.reserve(items);
+    }
+    fn update_value(&mut self, key: &PathTrace, next_value: Node) {
+        if self.exists(key) {
+            self.set(*key, next_value);
+        }
+    }
+    /// the nodes of the latest version
+    fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)> {
+        self.index
+            .keys()
+            .filter_map(|path| Some((*path, self.get(path)?)))
+    }
+    fn trigger_batch_actions(&mut self) {
+        self.try_trigger_batch_actions()
+            .expect("failed to write node versions");
+    }
+    fn remove_node(&mut self, key: PathTrace) {
+        self.try_remove_node(key)
+            .expect("failed to remove a node version");
+    }
+    fn try_set(&mut self, key: PathTrace, value: Node) -> Result<Option<Node>, MerkleError> {
+        let old = self.try_get(&key)?;
+        let versions = self.index.entry(key).or_default();
+        // a version only keeps the last write of each node
+        if let Some(&(written, removed)) = versions.last() {
+            if written == self.version {
+                versions.pop();
+                if removed {
+                    let removal = VersionedPath {
+                        path: key,
+                        version: written,
+                        removed,
+                    };
+                    self.store.try_remove_node(removal)?;
+                }
+            }
+        }
+        versions.push((self.version, false));
+        let versioned = VersionedPath {
+            path: key,
+            version: self.version,
+            removed: false,
+        };
+        self.store.try_set(versioned, value)?;
+        self.lookup.insert(value.data, key);
+        Ok(old)
+    }
+    fn try_get(&self, key: &PathTrace) -> Result<Option<Node>, MerkleError> {
+        self.try_get_at(key, u64::MAX)
+    }
+    fn try_get_key_by_hash(&self, hash: &Hash) -> Result<Option<PathTrace>, MerkleError> {
+        Ok(self.get_key_by_hash(hash))
+    }
+    fn try_trigger_batch_actions(&mut self) -> Result<(), MerkleError> {
+        self.store.try_trigger_batch_actions()
+    }
+    fn try_remove_node(&mut self, key: PathTrace) -> Result<(), MerkleError> {
+        let Some(old) = self.try_get(&key)? else {
+            return Ok(());
+        };
+        if self.lookup.get(&old.data) == Some(&key) {
+            self.lookup.remove(&old.data);
+        }
+        let versions = self.index.entry(key).or_default();
+        let versioned = |version, removed| VersionedPath {
+            path: key,
+            version,
+            removed,
+        };
+        // a node written in this version isn't kept
+        if versions.last() == Some(&(self.version, false)) {
+            versions.pop();
+            self.store.try_remove_node(versioned(self.version, false))?;
+        }
+        if versions.is_empty() {
+            self.index.remove(&key);
+        } else {
+            versions.push((self.version, true));
+            self.store
+                .try_set(versioned(self.version, true), Node::default())?;
+        }
+        Ok(())
+    }
+    /// completes the version being written
+    fn save_metadata(&mut self, metadata: &[u8]) -> Result<(), MerkleError> {
+        self.versions.push((self.version, metadata.to_vec()));
+        self.version += 1;
+        if let VersionRetention::Last(count) = self.retention {
+            if self.versions.len() > count.max(1) {
+                let oldest = self.versions[self.versions.len() - count.max(1)].0;
+                return self.prune_before(oldest);
+            }
+        }
+        let versions = self.encode_versions();
+        self.store.save_metadata(&versions)
+    }
+    /// the metadata of the latest version
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self.versions.last().map(|(_, metadata)| metadata.clone()))
+    }
+}
+
+/// The nodes of a `VersionedStore` as they were when a version was completed,
+/// only reads are supported and lookups by hash find nothing
+#[derive(Debug)]
+pub struct VersionView<'a, S> {
+    store: &'a VersionedStore<S>,
+    version: u64,
+}
+
+impl<S: NodeStore<VersionedPath>> NodeStore<PathTrace> for VersionView<'_, S> {
+    fn store_type(&self) -> StoreType {
+        self.store.store_type()
+    }
+    fn set(&mut self, _key: PathTrace, _value: Node) -> Option<Node> {
+        panic!("a version view is read-only")
+    }
+    fn get(&self, key: &PathTrace) -> Option<Node> {
+        self.try_get(key).ok().flatten()
+    }
+    fn get_key_by_hash(&self, _hash: &Hash) -> Option<PathTrace> {
+        None
+    }
+    fn sort(&mut self) {}
+    fn exists(&self, key: &PathTrace) -> bool {
+        self.get(key).is_some()
+    }
+    fn reserve(&mut self, _items: usize) {}
+    fn update_value(&mut self, _key: &PathTrace, _next_value: Node) {
+        panic!("a version view is read-only")
+    }
+    fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)> {
+        self.store
+            .index
+            .keys()
+            .filter_map(|path| Some((*path, self.get(path)?)))
+    }
+    fn trigger_batch_actions(&mut self) {}
+    fn remove_node(&mut self, _key: PathTrace) {
+        panic!("a version view is read-only")
+    }
+    fn try_get(&self, key: &PathTrace) -> Result<Option<Node>, MerkleError> {
+        self.store.try_get_at(key, self.version)
+    }
+    fn load_metadata(&self) -> Result<Option<Vec<u8>>, MerkleError> {
+        Ok(self.store.metadata_at(self.version).map(<[u8]>::to_vec))
+    }
+}
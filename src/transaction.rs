@@ -0,0 +1,26 @@
+use crate::hashers::Hasher;
+use crate::stores::StagedStore;
+use crate::{MerkleError, MerkleTree, NodeStore};
+
+impl<Store: NodeStore + Send + Sync, H: Hasher> MerkleTree<Store, H> {
+    /// Runs several mutations as one, eg. `tree.transaction(|tx| { tx.try_append(&a)?; tx.try_update_leaf(0, &b) })`.
+    /// The mutations run against a `StagedStore` over the current nodes, then their node writes
+    /// and the new metadata are committed at once, (in one atomic batch for persistent stores, see `NodeStore::try_commit`).
+    /// When the closure or the commit fails nothing is written and the tree is left as it was
+    pub fn transaction<R, F>(&mut self, transaction: F) -> Result<R, MerkleError>
+    where
+        F: for<'a> FnOnce(&mut MerkleTree<StagedStore<'a, Store>, H>) -> Result<R, MerkleError>,
+    {
+        let (result, metadata, writes) = {
+            let store = StagedStore::new(&self.tree_cache);
+            let mut staged = MerkleTree::with_metadata(self.metadata(), store);
+            let result = transaction(&mut staged)?;
+            (result, staged.metadata(), staged.tree_cache.into_writes())
+        };
+        if !writes.is_empty() {
+            self.tree_cache.try_commit(writes)?;
+            self.set_metadata(metadata);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,79 @@
+use super::{Hash, TreeLayout};
+use crate::hashers::HashAlgorithm;
+use crate::MerkleError;
+
+/// The in-memory state of a tree, persisted in its store so it can be reopened
+/// with `MerkleTree::open` without rehashing the leaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeMetadata {
+    pub root: Hash,
+    pub is_padded: bool,
+    pub leaf_count: usize,
+    pub level_count: isize,
+    pub lowest_level: isize,
+    pub unique_leaf_count: usize,
+    pub padding_start: usize,
+    pub layout: TreeLayout,
+    pub algorithm: HashAlgorithm,
+    pub domain_separated: bool,
+}
+
+impl TreeMetadata {
+    pub const VERSION: u8 = 1;
+    const SIZE: usize = 5 + 32 + 5 * 8;
+
+    /// `version | layout | is_padded | algorithm | domain_separated | root | leaf_count | level_count
+    /// | lowest_level | unique_leaf_count | padding_start`, integers as 8 byte little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend([
+            Self::VERSION,
+            self.layout as u8,
+            self.is_padded as u8,
+            self.algorithm as u8,
+            self.domain_separated as u8,
+        ]);
+        bytes.extend(self.root);
+        bytes.extend((self.leaf_count as u64).to_le_bytes());
+        bytes.extend((self.level_count as i64).to_le_bytes());
+        bytes.extend((self.lowest_level as i64).to_le_bytes());
+        bytes.extend((self.unique_leaf_count as u64).to_le_bytes());
+        bytes.extend((self.padding_start as u64).to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let invalid = |reason: &str| MerkleError::InvalidMetadata(reason.to_string());
+        if bytes.len() != Self::SIZE {
+            return Err(invalid("unexpected length"));
+        }
+        if bytes[0] != Self::VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        let layout = match bytes[1] {
+            0 => TreeLayout::Padded,
+            1 => TreeLayout::Unbalanced,
+            _ => return Err(invalid("unknown layout")),
+        };
+        let algorithm =
+            HashAlgorithm::from_u8(bytes[3]).ok_or_else(|| invalid("unknown hasher"))?;
+        let root: Hash = bytes[5..37].try_into().expect("length checked above");
+        let mut integers = bytes[37..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunks of 8")));
+        let mut next = || integers.next().expect("length checked above");
+        let to_usize = |value: u64| usize::try_from(value).map_err(|_| invalid("size overflow"));
+        Ok(Self {
+            root,
+            is_padded: bytes[2] != 0,
+            leaf_count: to_usize(next())?,
+            level_count: next() as i64 as isize,
+            lowest_level: next() as i64 as isize,
+            unique_leaf_count: to_usize(next())?,
+            padding_start: to_usize(next())?,
+            layout,
+            algorithm,
+            domain_separated: bytes[4] != 0,
+        })
+    }
+}
@@ -1,4 +1,5 @@
 pub use crate::stores::NodeStore;
+use crate::MerkleError;
 use itertools::{peek_nth, Itertools};
 #[cfg(any(
     feature = "sled",
@@ -7,8 +8,12 @@ use itertools::{peek_nth, Itertools};
     feature = "fjall"
 ))]
 use serde::{Deserialize, Serialize};
+mod metadata;
+mod root_builder;
 mod tree_construction;
-use crate::hashers::{GlobalHasher, Hasher};
+use crate::hashers::{HashAlgorithm, Hasher};
+pub use metadata::*;
+pub use root_builder::*;
 pub use tree_construction::*;
 pub type Data = Vec<u8>;
 pub type Hash = [u8; 32];
@@ -46,7 +51,7 @@ impl HashDirection {
         }
     }
     pub fn from_index(index: usize) -> Self {
-        if index % 2 == 0 {
+        if index.is_multiple_of(2) {
             return HashDirection::Left;
         }
         HashDirection::Right
@@ -70,8 +75,14 @@ pub struct Node {
     pub from_duplicate: bool,
 }
 impl Node {
-    pub fn new<I: AsRef<[u8]>>(data: I, is_leaf: bool) -> Self {
-        let data = GlobalHasher::hash_data(&data);
+    /// hashes the given data with `H`, eg. `Node::new::<GlobalHasher>(data, true)`
+    /// leaves are hashed with `Hasher::hash_leaf` so domain separated hashers prefix them
+    pub fn new<H: Hasher>(data: impl AsRef<[u8]>, is_leaf: bool) -> Self {
+        let data = if is_leaf {
+            H::hash_leaf(&data)
+        } else {
+            H::hash_data(&data)
+        };
         Self {
             is_leaf,
             data,
@@ -79,12 +90,22 @@ impl Node {
         }
     }
 }
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Proof {
     /// The hashes to use when verifying the proof
     /// The first element of the tuple is which side the hash should be on when concatinating
     /// Add level to the proof eases visualization of the proof
     pub hashes: Vec<(isize, HashDirection, Hash)>, // (level, direction, hash)
+    /// position of the proven leaf
+    pub leaf_index: usize,
+    /// number of leaves in the tree the proof was generated from, (including padding for padded trees)
+    pub leaf_count: usize,
+    /// root of the tree the proof was generated from
+    pub root: Hash,
+    /// hash function of the tree, (see `Hasher::ALGORITHM`)
+    pub algorithm: HashAlgorithm,
+    /// whether the tree's hasher is domain separated, (see `Hasher::DOMAIN_SEPARATED`)
+    pub domain_separated: bool,
 }
 impl Proof {
     pub fn get_proof_in_hex(&self) -> Vec<(isize, HashDirection, String)> {
@@ -93,6 +114,10 @@ impl Proof {
             .map(|(level, direction, hash)| (*level, *direction, hex::encode(hash)))
             .collect()
     }
+    /// the sibling hashes without levels or directions, (see `MerkleTree::verify_hashes_at`)
+    pub fn sibling_hashes(&self) -> Vec<Hash> {
+        self.hashes.iter().map(|(_, _, hash)| *hash).collect()
+    }
 }
 
 #[cfg_attr(
@@ -175,6 +200,72 @@ impl PartialOrd for PathTrace {
         Some(self.cmp(other))
     }
 }
+/// How leaves are laid out when the input is not a power of two
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TreeLayout {
+    /// pad up to the next power of two by repeating the last leaf
+    #[default]
+    Padded,
+    /// RFC 6962 layout, split at the largest power of two smaller than n with no duplicated leaves,
+    /// (an odd node out is promoted unchanged to the next level)
+    Unbalanced,
+}
+/// How `MerkleTree::remove` takes a leaf out of the tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemovalMode {
+    /// shift the later leaves down a position, (like `Vec::remove`)
+    #[default]
+    Compact,
+    /// keep every position and replace the leaf with `TOMBSTONE`
+    Tombstone,
+}
+/// the leaf hash left in place of a leaf removed with `RemovalMode::Tombstone`
+pub const TOMBSTONE: Hash = [0; 32];
+
+/// largest power of two strictly smaller than n, (n > 1)
+pub fn largest_power_of_two_below(n: usize) -> usize {
+    if n < 2 {
+        return 0;
+    }
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+/// the side of the sibling at every level of the route of a leaf, from the leaf up to the root,
+/// computed from the leaf position alone so verifiers don't have to trust the prover's directions.
+/// Levels where the node is promoted, (the odd node out in the unbalanced layout) have no sibling
+pub fn sibling_directions(
+    leaf_index: usize,
+    leaf_count: usize,
+) -> impl Iterator<Item = HashDirection> {
+    sibling_steps(leaf_index, leaf_count).map(|(_, direction)| direction)
+}
+/// `sibling_directions` with the level of every sibling, counted from the root at level 0
+pub fn sibling_steps(
+    mut leaf_index: usize,
+    leaf_count: usize,
+) -> impl Iterator<Item = (isize, HashDirection)> {
+    let mut width = leaf_count;
+    let mut level = get_level_count(leaf_count);
+    std::iter::from_fn(move || {
+        while width > 1 {
+            let sibling_level = level;
+            let direction = if !leaf_index.is_multiple_of(2) {
+                Some(HashDirection::Left)
+            } else if leaf_index + 1 < width {
+                Some(HashDirection::Right)
+            } else {
+                None
+            };
+            leaf_index /= 2;
+            width = width.div_ceil(2);
+            level -= 1;
+            if let Some(direction) = direction {
+                return Some((sibling_level, direction));
+            }
+        }
+        None
+    })
+}
 pub fn get_level_count(leaf_count: usize) -> isize {
     if leaf_count == 0 {
         return 0;
@@ -197,29 +288,40 @@ pub fn max_index_at_level_reversed(leaf_count: usize, depth: isize, level: isize
 }
 /// add padding to support unbalanced trees
 /// we resize to the nearest power of 2 and pad the last element
-pub fn pad_input<R, I>(
+pub fn pad_input<H, R, I>(
     input: I,
     size_hint: usize,
-) -> (usize, impl Iterator<Item = Node> + use<I, R>)
+) -> Result<(usize, impl Iterator<Item = Node> + use<H, I, R>), MerkleError>
 where
+    H: Hasher,
     R: AsRef<[u8]> + Clone,
     I: Iterator<Item = R>,
 {
+    pad_leaves(input.map(|data| Node::new::<H>(data, true)), size_hint)
+}
+/// `pad_input` for already hashed leaves
+pub fn pad_leaves<I: Iterator<Item = Node>>(
+    input: I,
+    size_hint: usize,
+) -> Result<(usize, impl Iterator<Item = Node> + use<I>), MerkleError> {
     let mut length = size_hint;
-    assert!(!length > 1, "can't support less than 2 inputs");
-    let input = input.map(|data| Node::new(data, true));
     let mut input = peek_nth(input);
-    let last = input.peek_nth(length.saturating_sub(1));
+    // nothing to pad an empty input with
+    let mut last = match length.checked_sub(1) {
+        Some(last_index) => *input
+            .peek_nth(last_index)
+            .ok_or(MerkleError::InputShorterThanSizeHint(size_hint))?,
+        None => Node::default(),
+    };
 
-    let fill_count = if !length.is_power_of_two() {
+    let fill_count = if length > 0 && !length.is_power_of_two() {
         length.next_power_of_two().saturating_sub(length)
     } else {
         0
     };
     length += fill_count;
-    let mut last = *last.unwrap();
     last.from_duplicate = true;
-    (length, input.pad_using(length, move |_| last))
+    Ok((length, input.pad_using(length, move |_| last)))
 }
 #[cfg(test)]
 mod path_trace {
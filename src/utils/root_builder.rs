@@ -0,0 +1,113 @@
+use super::{Hash, TreeLayout};
+use crate::hashers::{GlobalHasher, Hasher};
+use std::marker::PhantomData;
+
+/// Computes the root of a stream of leaves without storing any nodes, (eg. to check a snapshot
+/// too large to build a tree of). Completed subtrees are merged as leaves are pushed,
+/// so at most one pending hash per level, (log n) is kept.
+/// The root is the one `MerkleTree` constructs from the same input in the same layout
+#[derive(Debug, Clone)]
+pub struct RootBuilder<H: Hasher = GlobalHasher> {
+    layout: TreeLayout,
+    /// roots of the completed subtrees, (height, hash) with decreasing heights
+    pending: Vec<(u32, Hash)>,
+    /// the hash of the last leaf, padded trees repeat it up to the next power of two
+    last_leaf: Option<Hash>,
+    leaf_count: usize,
+    hasher: PhantomData<H>,
+}
+
+// constructors using the default hasher, (type parameter defaults are not used for inference)
+impl RootBuilder {
+    pub fn new(layout: TreeLayout) -> Self {
+        Self::with_hasher(layout)
+    }
+    /// the root of the tree `MerkleTree` constructs from `input` in `layout`
+    pub fn root_of_stream<D, I>(input: I, layout: TreeLayout) -> Hash
+    where
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = D>,
+    {
+        Self::root_of_stream_with_hasher(input, layout)
+    }
+}
+
+impl<H: Hasher> RootBuilder<H> {
+    /// a builder hashing with `H`, eg. `RootBuilder::<Blake3>::with_hasher(TreeLayout::Padded)`
+    pub fn with_hasher(layout: TreeLayout) -> Self {
+        Self {
+            layout,
+            pending: Vec::new(),
+            last_leaf: None,
+            leaf_count: 0,
+            hasher: PhantomData,
+        }
+    }
+    pub fn root_of_stream_with_hasher<D, I>(input: I, layout: TreeLayout) -> Hash
+    where
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = D>,
+    {
+        let mut builder = Self::with_hasher(layout);
+        input.into_iter().for_each(|data| builder.push(&data));
+        builder.finish()
+    }
+    /// number of leaves pushed, (without padding)
+    pub fn len(&self) -> usize {
+        self.leaf_count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+    /// adds the next leaf, merging every subtree it completes
+    pub fn push<D: AsRef<[u8]>>(&mut self, data: &D) {
+        let leaf = H::hash_leaf(data);
+        self.last_leaf = Some(leaf);
+        self.leaf_count += 1;
+        let mut node = (0, leaf);
+        while let Some(&(height, left)) = self.pending.last() {
+            if height != node.0 {
+                break;
+            }
+            self.pending.pop();
+            node = (height + 1, H::hash_concat(&left, &node.1));
+        }
+        self.pending.push(node);
+    }
+    /// the root of the pushed leaves, the root of an empty tree is the hash of no data
+    pub fn finish(self) -> Hash {
+        let Some(last_leaf) = self.last_leaf else {
+            return H::hash_data(&[]);
+        };
+        let mut pending = self.pending.into_iter().rev();
+        let (mut height, mut root) = pending.next().expect("a leaf was pushed");
+        match self.layout {
+            // the subtrees are the children of the root and its right descendants, (RFC 6962)
+            TreeLayout::Unbalanced => {
+                pending.fold(root, |right, (_, left)| H::hash_concat(&left, &right))
+            }
+            // every subtree still pending is followed by one of only padding, (copies of the last leaf)
+            TreeLayout::Padded => {
+                let mut padding = (0, last_leaf);
+                for (left_height, left) in pending {
+                    while height < left_height {
+                        while padding.0 < height {
+                            padding = (padding.0 + 1, H::hash_concat(&padding.1, &padding.1));
+                        }
+                        root = H::hash_concat(&root, &padding.1);
+                        height += 1;
+                    }
+                    root = H::hash_concat(&left, &root);
+                    height += 1;
+                }
+                root
+            }
+        }
+    }
+}
+
+impl<D: AsRef<[u8]>, H: Hasher> Extend<D> for RootBuilder<H> {
+    fn extend<I: IntoIterator<Item = D>>(&mut self, input: I) {
+        input.into_iter().for_each(|data| self.push(&data));
+    }
+}
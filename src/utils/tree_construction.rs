@@ -1,14 +1,15 @@
-use super::{HashDirection, Node, NodeStore, PathTrace};
-use crate::hashers::{GlobalHasher, Hasher};
+use super::{largest_power_of_two_below, HashDirection, Node, NodeStore, PathTrace};
+use crate::hashers::Hasher;
+use crate::MerkleError;
 use crossbeam_queue::SegQueue;
-pub fn build_tree<S: NodeStore + Send>(
+pub fn build_tree<S: NodeStore + Send, H: Hasher>(
     tree_cache: &mut S,
     input: impl IntoIterator<Item = Node>,
     level_count: isize,
     lowest_level: isize,
     is_rebuild: bool,
     last_index: usize,
-) -> (PathTrace, Node, usize) {
+) -> Result<(PathTrace, Node, usize), MerkleError> {
     let mut previous: Option<Node> = None;
     let mut unique_count = 1;
     let nodes = SegQueue::new();
@@ -25,34 +26,45 @@ pub fn build_tree<S: NodeStore + Send>(
         previous.replace(data);
         nodes.push((path, data));
     });
+    // a single leaf is the root, (unless it's being added to an existing tree)
+    if nodes.len() == 1 {
+        if let Some((mut path, node)) = nodes.pop() {
+            if level_count == lowest_level && !is_rebuild {
+                path = PathTrace::root(lowest_level);
+            }
+            tree_cache.try_set(path, node)?;
+            tree_cache.try_trigger_batch_actions()?;
+            return Ok((path, node, unique_count));
+        }
+    }
     let parallelize = level_count > 14;
     if parallelize {
         let generated: SegQueue<(PathTrace, Node)> = SegQueue::new();
-        let result = build_parallel(nodes, &generated, lowest_level, is_rebuild);
+        let result = build_parallel::<H>(nodes, &generated, lowest_level, is_rebuild);
         for (path, node) in generated {
-            tree_cache.set(path, node);
+            tree_cache.try_set(path, node)?;
         }
-        tree_cache.trigger_batch_actions();
+        tree_cache.try_trigger_batch_actions()?;
 
-        return (result.0, result.1, unique_count);
+        return Ok((result.0, result.1, unique_count));
     }
 
-    let result = build_sequential(tree_cache, nodes, lowest_level, is_rebuild);
-    (result.0, result.1, unique_count)
+    let result = build_sequential::<S, H>(tree_cache, nodes, lowest_level, is_rebuild)?;
+    Ok((result.0, result.1, unique_count))
 }
 // build the tree  sequentiallly
-fn build_sequential<S: NodeStore + Send>(
+fn build_sequential<S: NodeStore + Send, H: Hasher>(
     tree_cache: &mut S,
     mut nodes: SegQueue<(PathTrace, Node)>,
     lowest_level: isize,
     is_rebuild: bool,
-) -> (PathTrace, Node) {
+) -> Result<(PathTrace, Node), MerkleError> {
     while nodes.len() > 1 {
         //  reduce allocations as length of nodes to process halves at every level up.
         let next_level = SegQueue::new();
         let mut cursor = nodes.into_iter();
         while let Some((left, node)) = cursor.next() {
-            let (right, right_node) = cursor.next().unwrap_or((left, node));
+            let right = cursor.next();
 
             let level = left.level - 1;
 
@@ -68,25 +80,33 @@ fn build_sequential<S: NodeStore + Send>(
                 parent_index = 1;
             }
 
+            let data = match right {
+                Some((_, right_node)) => H::hash_concat(&node.data, &right_node.data),
+                // the odd node out is promoted unchanged to the next level (RFC 6962 layout),
+                // padded inputs are always a power of two so never get here
+                None => node.data,
+            };
             let parent_node = Node {
-                data: GlobalHasher::hash_concat(&node.data, &right_node.data),
+                data,
                 is_leaf: false,
                 from_duplicate: node.from_duplicate,
             };
             let parent = PathTrace::new(direction, level, parent_index);
-            tree_cache.set(left, node);
-            tree_cache.set(right, right_node);
-            tree_cache.set(parent, parent_node);
+            tree_cache.try_set(left, node)?;
+            if let Some((right, right_node)) = right {
+                tree_cache.try_set(right, right_node)?;
+            }
+            tree_cache.try_set(parent, parent_node)?;
             next_level.push((parent, parent_node));
         }
         nodes = next_level;
     }
-    tree_cache.trigger_batch_actions();
-    nodes.pop().unwrap_or_default()
+    tree_cache.try_trigger_batch_actions()?;
+    Ok(nodes.pop().unwrap_or_default())
 }
 
 /// build the tree in parallel using divide and conquer
-fn build_parallel(
+fn build_parallel<H: Hasher>(
     nodes: SegQueue<(PathTrace, Node)>,
     output_buffer: &SegQueue<(PathTrace, Node)>,
     lowest_level: isize,
@@ -99,7 +119,8 @@ fn build_parallel(
         }
     }
 
-    let mid = nodes.len() / 2;
+    // split at the largest power of two smaller than the input, (the middle for padded inputs)
+    let mid = largest_power_of_two_below(nodes.len());
     let left_slice = SegQueue::new();
     for _ in 0..mid {
         if let Some(value) = nodes.pop() {
@@ -108,14 +129,25 @@ fn build_parallel(
     }
 
     let (left_result, right_result) = rayon::join(
-        || build_parallel(left_slice, output_buffer, lowest_level, is_rebuild),
-        || build_parallel(nodes, output_buffer, lowest_level, is_rebuild),
+        || build_parallel::<H>(left_slice, output_buffer, lowest_level, is_rebuild),
+        || build_parallel::<H>(nodes, output_buffer, lowest_level, is_rebuild),
     );
 
     let (left_path, left_node) = left_result;
-    let (_right_path, right_node) = right_result;
+    let (mut right_path, mut right_node) = right_result;
+    // a smaller right subtree is promoted up to the level of the left one
+    while right_path.level > left_path.level {
+        let index = right_path.index / 2;
+        right_path = PathTrace::new(
+            HashDirection::from_index(index),
+            right_path.level - 1,
+            index,
+        );
+        right_node.is_leaf = false;
+        output_buffer.push((right_path, right_node));
+    }
 
-    let parent_hash = GlobalHasher::hash_concat(&left_node.data, &right_node.data);
+    let parent_hash = H::hash_concat(&left_node.data, &right_node.data);
     let parent_level_in_tree = left_path.level - 1;
     let mut parent_index = left_path.index / 2;
 
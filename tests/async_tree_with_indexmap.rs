@@ -0,0 +1,117 @@
+#[cfg(feature = "async")]
+#[cfg(test)]
+mod async_tree_with_indexmap_store {
+    use merkle_tree::{
+        async_tree::AsyncMerkleTree,
+        example_data,
+        hashers::{GlobalHasher, Hasher},
+        stores::{Blocking, TreeCache},
+        MerkleTree, TreeLayout,
+    };
+    type Tree = MerkleTree<TreeCache>;
+    #[tokio::test]
+    async fn construction_matches_the_sync_tree() {
+        for size in [0, 1, 2, 5, 8, 13] {
+            let data = example_data(size);
+            for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+                let store = Blocking::new(TreeCache::new());
+                let tree = AsyncMerkleTree::<_>::construct_with_layout(data.clone(), layout, store)
+                    .await
+                    .unwrap();
+                let expected = Tree::construct_with_layout(&data, layout, TreeCache::new());
+                assert_eq!(tree.metadata(), expected.metadata());
+                for (i, leaf) in data.iter().enumerate() {
+                    let proof = tree.prove(leaf).await.unwrap().unwrap();
+                    assert_eq!(proof.leaf_index, i);
+                    assert!(Tree::verify_proof_at(
+                        i,
+                        proof.leaf_count,
+                        leaf,
+                        &proof,
+                        tree.root()
+                    ));
+                }
+                assert_eq!(tree.prove(&vec![255]).await.unwrap(), None);
+            }
+        }
+    }
+    #[tokio::test]
+    async fn construction_commits_in_batches() {
+        // more nodes than fit in one batch
+        let data = example_data(10_000);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store = Blocking::new(TreeCache::new());
+            let tree = AsyncMerkleTree::<_>::construct_with_layout(data.clone(), layout, store)
+                .await
+                .unwrap();
+            let expected = Tree::construct_with_layout(&data, layout, TreeCache::new());
+            assert_eq!(tree.metadata(), expected.metadata());
+            for index in [0, 4095, 8191, 9999] {
+                let proof = tree.prove_index(index).await.unwrap();
+                assert_eq!(proof, expected.prove_index(index), "{layout:?} {index}");
+            }
+        }
+    }
+    #[tokio::test]
+    async fn appends_and_updates_match_the_sync_tree() {
+        let data = example_data(20);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store = Blocking::new(TreeCache::new());
+            let mut tree =
+                AsyncMerkleTree::<_>::construct_with_layout(Vec::<Vec<u8>>::new(), layout, store)
+                    .await
+                    .unwrap();
+            let mut expected = Tree::construct_with_layout(&data[..0], layout, TreeCache::new());
+            for leaf in &data[..9] {
+                tree.append(leaf).await.unwrap();
+                expected.append(leaf);
+                assert_eq!(tree.root(), expected.root(), "{layout:?}");
+            }
+            tree.append_batch(&data[9..]).await.unwrap();
+            expected.append_batch(&data[9..]);
+            assert_eq!(tree.metadata(), expected.metadata());
+
+            for index in [0, 7, 19] {
+                let old = tree.update_leaf(index, &vec![100]).await.unwrap();
+                assert_eq!(Some(old), expected.update_leaf(index, &vec![100]));
+                assert_eq!(tree.root(), expected.root(), "{layout:?} {index}");
+            }
+            let error = tree.update_leaf(20, &vec![100]).await.unwrap_err();
+            assert!(matches!(
+                error,
+                merkle_tree::MerkleError::LeafIndexOutOfRange(20)
+            ));
+            let proof = tree.prove_index(7).await.unwrap().unwrap();
+            assert_eq!(Some(proof), expected.prove_index(7));
+
+            let (target, new) = (
+                GlobalHasher::hash_leaf(&data[3]),
+                GlobalHasher::hash_leaf(&[101]),
+            );
+            tree.update(&target, new).await.unwrap();
+            expected.update(&target, new);
+            assert_eq!(tree.metadata(), expected.metadata());
+            let error = tree.update(&target, new).await.unwrap_err();
+            assert!(matches!(error, merkle_tree::MerkleError::HashNotFound(_)));
+        }
+    }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serve_proofs_from_tasks() {
+        let data = example_data(64);
+        let store = Blocking::new(TreeCache::new());
+        let tree = AsyncMerkleTree::<_>::construct(data.clone(), store)
+            .await
+            .unwrap();
+        let tree = std::sync::Arc::new(tree);
+        let tasks: Vec<_> = (0..data.len())
+            .map(|i| {
+                let tree = tree.clone();
+                tokio::spawn(async move { tree.prove_index(i).await })
+            })
+            .collect();
+        for (i, task) in tasks.into_iter().enumerate() {
+            let proof = task.await.unwrap().unwrap().unwrap();
+            assert!(Tree::verify_proof_at(i, 64, &data[i], &proof, tree.root()));
+        }
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod chunked_tree_with_indexmap_store {
+    use indexmap::IndexMap;
+    use merkle_tree::{
+        chunked::{ChunkedTree, RangeProof},
+        MerkleTree, Node, PathTrace, TreeLayout,
+    };
+    type Tree = ChunkedTree<IndexMap<PathTrace, Node>>;
+    fn bytes(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+    fn chunked(input: &[u8], chunk_size: usize, layout: TreeLayout) -> Tree {
+        ChunkedTree::from_reader_with_layout(input, chunk_size, layout, IndexMap::new()).unwrap()
+    }
+    #[test]
+    fn roots_match_trees_of_the_chunks() {
+        for len in [0, 1, 63, 64, 65, 1000, 4096, 70_000] {
+            let input = bytes(len);
+            for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+                let tree = chunked(&input, 64, layout);
+                let expected = MerkleTree::<_>::construct_with_layout(
+                    input.chunks(64),
+                    layout,
+                    IndexMap::new(),
+                );
+                assert_eq!(tree.tree.root(), expected.root(), "{len} {layout:?}");
+                assert_eq!(tree.byte_len(), len as u64);
+                assert_eq!(tree.chunk_count(), len.div_ceil(64));
+            }
+        }
+        let error = ChunkedTree::from_reader(&[1_u8][..], 0, IndexMap::new()).unwrap_err();
+        assert!(matches!(error, merkle_tree::MerkleError::Io(_)));
+    }
+    #[test]
+    fn byte_range_proofs() {
+        let input = bytes(1000);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let tree = chunked(&input, 64, layout);
+            let root = tree.tree.root();
+            for range in [0..1, 0..1000, 10..20, 60..70, 64..128, 900..1000, 999..1000] {
+                let proof = tree.prove_range(range.clone()).unwrap();
+                let span = proof.chunk_span();
+                let chunks = &input[span.start as usize..span.end as usize];
+                assert!(
+                    Tree::verify_range(64, 1000, chunks, &proof, root),
+                    "{range:?}"
+                );
+                let offset = (range.start - span.start) as usize;
+                assert_eq!(
+                    &chunks[offset..offset + range.clone().count()],
+                    &input[range.start as usize..range.end as usize]
+                );
+
+                let mut tampered = chunks.to_vec();
+                tampered[0] ^= 1;
+                assert!(!Tree::verify_range(64, 1000, &tampered, &proof, root));
+                assert!(!Tree::verify_range(64, 1000, &chunks[1..], &proof, root));
+                // the chunks have to be the ones at the proven position
+                let shifted = RangeProof {
+                    range: range.start + 64..range.end + 64,
+                    ..proof.clone()
+                };
+                assert!(
+                    range.end + 64 > 1000 || !Tree::verify_range(64, 1000, chunks, &shifted, root)
+                );
+            }
+            assert_eq!(tree.prove_range(5..5), None);
+            assert_eq!(tree.prove_range(900..1001), None);
+        }
+        // a chunk moved to another position of a claimed shorter input
+        let tree = chunked(b"abcde", 1, TreeLayout::Unbalanced);
+        let root = tree.tree.root();
+        let mut moved = tree.prove_range(4..5).unwrap();
+        moved.byte_len = 3;
+        moved.range = 2..3;
+        moved.proof.leaf_indexes = vec![2];
+        assert!(Tree::verify_range(1, 3, b"e", &moved, root));
+        assert!(!Tree::verify_range(1, 5, b"e", &moved, root));
+        let proof = tree.prove_range(4..5).unwrap();
+        assert!(Tree::verify_range(1, 5, b"e", &proof, root));
+        assert!(!Tree::verify_range(2, 5, b"e", &proof, root));
+    }
+    #[test]
+    fn chunk_a_file() {
+        let input = bytes(10_000);
+        let path = std::env::temp_dir().join(format!("chunked-{}", std::process::id()));
+        std::fs::write(&path, &input).unwrap();
+        let tree = ChunkedTree::from_path(&path, 1024, IndexMap::new()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tree.chunk_count(), 10);
+        assert_eq!(
+            tree.tree.root(),
+            chunked(&input, 1024, TreeLayout::Padded).tree.root()
+        );
+        let missing = ChunkedTree::from_path(&path, 1024, IndexMap::new());
+        assert!(matches!(missing, Err(merkle_tree::MerkleError::Io(_))));
+    }
+    #[test]
+    #[cfg(feature = "blake3")]
+    fn chunk_with_blake3() {
+        use merkle_tree::hashers::Blake3;
+        let input = bytes(5000);
+        let tree = ChunkedTree::<_, Blake3>::from_reader_with_layout(
+            &input[..],
+            1024,
+            TreeLayout::Padded,
+            IndexMap::new(),
+        )
+        .unwrap();
+        assert_eq!(tree.tree.len(), 5);
+        let proof = tree.prove_range(4096..5000).unwrap();
+        assert!(ChunkedTree::<IndexMap<_, _>, Blake3>::verify_range(
+            1024,
+            5000,
+            &input[4096..],
+            &proof,
+            tree.tree.root()
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod mmr_with_indexmap_store {
+    use indexmap::IndexMap;
+    use merkle_tree::{
+        example_data,
+        hashers::{GlobalHasher, Hasher},
+        mmr::{MerkleMountainRange, MmrProof},
+        MerkleTree, Node, PathTrace,
+    };
+    type Range = MerkleMountainRange<IndexMap<PathTrace, Node>>;
+    fn range_of(data: &[Vec<u8>]) -> Range {
+        let mut range = MerkleMountainRange::new(IndexMap::new()).unwrap();
+        for (i, leaf) in data.iter().enumerate() {
+            assert_eq!(range.append(leaf), i);
+        }
+        range
+    }
+    #[test]
+    fn empty_range() {
+        let range = MerkleMountainRange::new(IndexMap::new()).unwrap();
+        assert!(range.is_empty());
+        assert!(range.peaks().is_empty());
+        assert_eq!(range.root(), &Range::bag_peaks(&[]));
+        assert_eq!(range.prove(0), None);
+    }
+    #[test]
+    fn single_mountains_match_dense_trees() {
+        for size in [1, 2, 4, 8, 32] {
+            let data = example_data(size);
+            let range = range_of(&data);
+            let tree = MerkleTree::<_>::construct(&data, IndexMap::new());
+            assert_eq!(range.peaks().len(), 1);
+            assert_eq!(range.root(), tree.root());
+        }
+    }
+    #[test]
+    fn appends_store_no_padding() {
+        for size in 1..40 {
+            let range = range_of(&example_data(size));
+            let mountains = size.count_ones() as usize;
+            assert_eq!(range.len(), size);
+            assert_eq!(range.peaks().len(), mountains);
+            assert_eq!(range.tree_cache.len(), 2 * size - mountains);
+            assert_eq!(range.root(), &Range::bag_peaks(&range.peaks()));
+        }
+    }
+    #[test]
+    fn every_leaf_proof_verifies() {
+        for size in [1, 3, 7, 12, 33] {
+            let data = example_data(size);
+            let range = range_of(&data);
+            for (i, leaf) in data.iter().enumerate() {
+                let proof = range.prove(i).unwrap();
+                assert!(Range::verify_proof(size, leaf, &proof, range.root()));
+                assert!(!Range::verify_proof(size, &vec![255], &proof, range.root()));
+                let moved = MmrProof {
+                    leaf_index: (i + 1) % size,
+                    ..proof.clone()
+                };
+                assert!(size == 1 || !Range::verify_proof(size, leaf, &moved, range.root()));
+                let mut extra = proof.clone();
+                extra.siblings.push([1; 32]);
+                assert!(!Range::verify_proof(size, leaf, &extra, range.root()));
+                let mut peaks = proof.clone();
+                peaks.peaks.push([1; 32]);
+                assert!(!Range::verify_proof(size, leaf, &peaks, range.root()));
+            }
+            assert_eq!(range.prove(size), None);
+        }
+        // the last leaf of 7 at index 5 of 6 leaves, the peaks bag to the same root
+        let data = example_data(7);
+        let range = range_of(&data);
+        let peaks = range.peaks();
+        let forged = MmrProof {
+            leaf_index: 5,
+            leaf_count: 6,
+            siblings: vec![peaks[1]],
+            peaks: vec![peaks[0], GlobalHasher::hash_concat(&peaks[1], &peaks[2])],
+        };
+        assert!(Range::verify_proof(6, &data[6], &forged, range.root()));
+        assert!(!Range::verify_proof(7, &data[6], &forged, range.root()));
+    }
+    #[test]
+    fn old_proofs_verify_against_old_roots() {
+        let data = example_data(20);
+        let mut range = range_of(&data[..5]);
+        let root = *range.root();
+        let proof = range.prove(2).unwrap();
+        for leaf in &data[5..] {
+            range.append(leaf);
+        }
+        assert!(Range::verify_proof(5, &data[2], &proof, &root));
+        assert!(!Range::verify_proof(20, &data[2], &proof, range.root()));
+        let proof = range.prove(2).unwrap();
+        assert!(Range::verify_proof(20, &data[2], &proof, range.root()));
+    }
+}
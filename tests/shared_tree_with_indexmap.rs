@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod shared_tree_with_indexmap_store {
+    use merkle_tree::{
+        example_data, shared::SharedMerkleTree, stores::TreeCache, MerkleError, MerkleTree,
+        TreeLayout,
+    };
+    type Tree = MerkleTree<TreeCache>;
+    #[test]
+    fn writes_match_the_unshared_tree() {
+        let data = example_data(30);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let tree = Tree::construct_with_layout(&data[..5], layout, TreeCache::new());
+            let shared = SharedMerkleTree::new(tree);
+            let mut expected = Tree::construct_with_layout(&data[..5], layout, TreeCache::new());
+            for leaf in &data[5..12] {
+                shared.append(leaf).unwrap();
+                expected.append(leaf);
+            }
+            shared.append_batch(&data[12..]).unwrap();
+            expected.append_batch(&data[12..]);
+            assert_eq!(
+                shared.update_leaf(3, &vec![103]).unwrap(),
+                expected.update_leaf(3, &vec![103]).unwrap()
+            );
+            shared.write(|tree| tree.try_truncate(25)).unwrap();
+            expected.truncate(25);
+
+            let snapshot = shared.snapshot();
+            assert_eq!(snapshot.epoch, 10);
+            assert_eq!(snapshot.metadata, expected.metadata());
+            assert_eq!(shared.root(), *expected.root());
+            for (i, leaf) in data[..25].iter().enumerate().skip(4) {
+                assert_eq!(shared.prove(leaf), expected.prove(leaf), "{layout:?} {i}");
+            }
+            assert_eq!(shared.prove(&data[3]), None);
+
+            // a failed write leaves the tree and its snapshot as they were
+            let error = shared.update_leaf(25, &vec![125]).unwrap_err();
+            assert!(matches!(error, MerkleError::LeafIndexOutOfRange(25)));
+            let failed = shared.write(|tree| {
+                tree.try_append(&vec![200])?;
+                Err::<(), _>(MerkleError::MissingMetadata)
+            });
+            assert!(failed.is_err());
+            assert_eq!(shared.snapshot(), snapshot);
+            assert_eq!(shared.prove(&vec![200]), None);
+            let tree = shared.into_inner();
+            assert_eq!(tree.metadata(), expected.metadata());
+        }
+    }
+    #[test]
+    fn readers_prove_while_a_writer_appends() {
+        let data = example_data(300);
+        let shared = SharedMerkleTree::new(Tree::construct(&data[..10], TreeCache::new()));
+        std::thread::scope(|scope| {
+            for reader in 0..4 {
+                let (shared, data) = (&shared, &data);
+                scope.spawn(move || {
+                    for round in 0..500 {
+                        let snapshot = shared.snapshot();
+                        let index = (round * 7 + reader) % snapshot.metadata.padding_start.max(1);
+                        let proof = shared.prove_index(index).unwrap();
+                        // every proof is consistent with the root it was generated against
+                        assert!(Tree::verify_proof_at(
+                            index,
+                            proof.leaf_count,
+                            &data[index],
+                            &proof,
+                            &proof.root
+                        ));
+                        assert!(shared.snapshot().epoch >= snapshot.epoch);
+                    }
+                });
+            }
+            for leaf in &data[10..] {
+                shared.append(leaf).unwrap();
+            }
+        });
+        let expected = Tree::construct(&data, TreeCache::new());
+        assert_eq!(shared.root(), *expected.root());
+        assert_eq!(shared.snapshot().epoch, 290);
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod sparse_tree_with_indexmap_store {
+    use indexmap::IndexMap;
+    use merkle_tree::{
+        hashers::{GlobalHasher, Hasher},
+        sparse::{SparseMerkleTree, SparsePath},
+        Hash, Node,
+    };
+    type Tree = SparseMerkleTree<IndexMap<SparsePath, Node>>;
+    fn key(i: u8) -> Hash {
+        GlobalHasher::hash_data(&[i])
+    }
+    #[test]
+    fn empty_tree() {
+        let tree = SparseMerkleTree::new(IndexMap::new()).unwrap();
+        assert_eq!(tree.root(), &Tree::empty_root());
+        assert!(tree.is_empty());
+        assert_eq!(tree.get(&key(0)), None);
+        let proof = tree.prove(&key(0));
+        assert!(proof.siblings.is_empty());
+        assert!(Tree::verify_non_inclusion(&key(0), &proof, tree.root()));
+        assert!(!Tree::verify_inclusion(
+            &key(0),
+            &vec![0],
+            &proof,
+            tree.root()
+        ));
+    }
+    #[test]
+    fn inclusion_and_non_inclusion_proofs() {
+        let mut tree = SparseMerkleTree::new(IndexMap::new()).unwrap();
+        // the keys at both ends of the key space share no siblings
+        let keys: Vec<_> = (0..20).map(key).chain([[0; 32], [0xff; 32]]).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(tree.insert(*key, &vec![i as u8]), None);
+        }
+        for (i, key) in keys.iter().enumerate() {
+            let value = vec![i as u8];
+            assert_eq!(tree.get(key), Some(GlobalHasher::hash_leaf(&value)));
+            let proof = tree.prove(key);
+            assert!(Tree::verify_inclusion(key, &value, &proof, tree.root()));
+            assert!(!Tree::verify_inclusion(
+                key,
+                &vec![100],
+                &proof,
+                tree.root()
+            ));
+            assert!(!Tree::verify_non_inclusion(key, &proof, tree.root()));
+        }
+        for absent in (20..40).map(key) {
+            let proof = tree.prove(&absent);
+            assert!(Tree::verify_non_inclusion(&absent, &proof, tree.root()));
+            assert!(!Tree::verify_non_inclusion(&keys[0], &proof, tree.root()));
+            let mut extra = proof.clone();
+            extra.siblings.push([1; 32]);
+            assert!(!Tree::verify_non_inclusion(&absent, &extra, tree.root()));
+        }
+    }
+    #[test]
+    fn root_is_independent_of_insertion_order() {
+        let mut tree = SparseMerkleTree::new(IndexMap::new()).unwrap();
+        let mut reversed = SparseMerkleTree::new(IndexMap::new()).unwrap();
+        for i in 0..30 {
+            tree.insert(key(i), &vec![i]);
+            reversed.insert(key(29 - i), &vec![29 - i]);
+        }
+        assert_eq!(tree.root(), reversed.root());
+    }
+    #[test]
+    fn update_and_remove() {
+        let mut tree = SparseMerkleTree::new(IndexMap::new()).unwrap();
+        for i in 0..10 {
+            tree.insert(key(i), &vec![i]);
+        }
+        let root = *tree.root();
+        let old = tree.insert(key(3), &vec![103]);
+        assert_eq!(old, Some(GlobalHasher::hash_leaf(&vec![3])));
+        assert_ne!(tree.root(), &root);
+        tree.insert(key(3), &vec![3]);
+        assert_eq!(tree.root(), &root);
+
+        tree.insert(key(50), &vec![50]);
+        assert_eq!(
+            tree.remove(&key(50)),
+            Some(GlobalHasher::hash_leaf(&vec![50]))
+        );
+        assert_eq!(tree.root(), &root);
+        assert_eq!(tree.remove(&key(50)), None);
+        let proof = tree.prove(&key(50));
+        assert!(Tree::verify_non_inclusion(&key(50), &proof, tree.root()));
+
+        // empty subtrees aren't stored
+        for i in 0..10 {
+            tree.remove(&key(i));
+        }
+        assert_eq!(tree.root(), &Tree::empty_root());
+        assert!(tree.tree_cache.is_empty());
+    }
+}
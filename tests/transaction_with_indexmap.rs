@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod transaction_with_indexmap_store {
+    use merkle_tree::{
+        example_data, stores::TreeCache, MerkleError, MerkleTree, NodeStore, TreeLayout,
+    };
+    type Tree = MerkleTree<TreeCache>;
+    #[test]
+    fn transactions_match_the_mutations_they_run() {
+        let data = example_data(24);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let mut tree = Tree::construct_with_layout(&data[..6], layout, TreeCache::new());
+            let mut expected = Tree::construct_with_layout(&data[..6], layout, TreeCache::new());
+            let old = tree
+                .transaction(|tx| {
+                    for leaf in &data[6..10] {
+                        tx.try_append(leaf)?;
+                    }
+                    tx.try_append_batch(&data[10..])?;
+                    tx.try_update_leaf(1, &vec![101])
+                })
+                .unwrap();
+            for leaf in &data[6..10] {
+                expected.append(leaf);
+            }
+            expected.append_batch(&data[10..]);
+            assert_eq!(Some(old), expected.update_leaf(1, &vec![101]));
+
+            assert_eq!(tree.metadata(), expected.metadata());
+            assert_eq!(
+                tree.tree_cache.entries().count(),
+                expected.tree_cache.entries().count()
+            );
+            for (i, leaf) in data.iter().enumerate().skip(2) {
+                assert_eq!(tree.prove(leaf), expected.prove(leaf), "{layout:?} {i}");
+            }
+            assert_eq!(tree.prove(&data[1]), None);
+            assert!(tree.prove(&vec![101]).is_some());
+            // a transaction without writes changes nothing
+            assert_eq!(
+                tree.transaction(|tx| Ok(*tx.root())).unwrap(),
+                *expected.root()
+            );
+            assert_eq!(tree.metadata(), expected.metadata());
+        }
+    }
+    #[test]
+    fn failed_transactions_are_rolled_back() {
+        let data = example_data(12);
+        let mut tree = Tree::construct(&data[..7], TreeCache::new());
+        let metadata = tree.metadata();
+        let nodes: Vec<_> = tree.tree_cache.entries().collect();
+        let error = tree
+            .transaction(|tx| {
+                tx.try_append_batch(&data[7..])?;
+                tx.try_update_leaf(0, &vec![100])?;
+                tx.try_update_leaf(40, &vec![140])
+            })
+            .unwrap_err();
+        assert!(matches!(error, MerkleError::LeafIndexOutOfRange(40)));
+        assert_eq!(tree.metadata(), metadata);
+        assert_eq!(tree.tree_cache.entries().collect::<Vec<_>>(), nodes);
+        assert_eq!(tree.prove(&data[8]), None);
+        assert!(tree.prove(&data[0]).is_some());
+    }
+}
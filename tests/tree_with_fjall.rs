@@ -6,7 +6,7 @@ mod tree_with_fjall {
         example_data,
         hashers::{GlobalHasher, Hasher},
         stores::{temporary_fjall_db, FjallDbStore, NodeStore},
-        HashDirection, MerkleTree, PathTrace,
+        HashDirection, MerkleTree, PathTrace, TreeLayout,
     };
 
     use std::sync::LazyLock;
@@ -197,4 +197,111 @@ mod tree_with_fjall {
             ));
         }
     }
+    #[test]
+    fn unbalanced_append_and_prove() {
+        (2_usize..20).for_each(|size| {
+            let data = example_data(size);
+            let store = FjallDbStore::new(
+                LazyLock::force(&FJALL_DB),
+                format!("unbalanced_append-{size}"),
+            )
+            .unwrap();
+            let mut tree =
+                MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, store);
+            let input: Vec<_> = (112..130).map(|d| vec![d]).collect();
+            for h in input.iter() {
+                tree.append(h);
+                let proof = tree.prove(h).unwrap();
+                assert!(MerkleTree::<FjallDbStore>::verify_proof(
+                    h,
+                    &proof,
+                    tree.root()
+                ))
+            }
+            assert_eq!(tree.leaf_count, size + input.len());
+        });
+    }
+    #[test]
+    fn reopen_a_persisted_tree() {
+        let data = example_data(11);
+        let extra = vec![100];
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let name = format!("reopen-{layout:?}");
+            let store = FjallDbStore::new(LazyLock::force(&FJALL_DB), name.as_str()).unwrap();
+            let tree = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            let metadata = tree.metadata();
+            drop(tree);
+
+            let store = FjallDbStore::new(LazyLock::force(&FJALL_DB), name.as_str()).unwrap();
+            let mut tree = MerkleTree::open(store).unwrap();
+            assert_eq!(tree.metadata(), metadata);
+            tree.append(&extra);
+
+            let mut input = data.clone();
+            input.push(extra.clone());
+            let store =
+                FjallDbStore::new(LazyLock::force(&FJALL_DB), format!("{name}-expected")).unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&input, layout, store);
+            assert_eq!(tree.root(), expected.root());
+            let proof = tree.prove(&extra).unwrap();
+            assert!(MerkleTree::<FjallDbStore>::verify_proof(
+                &extra,
+                &proof,
+                tree.root()
+            ));
+        }
+    }
+    #[test]
+    fn commit_a_transaction() {
+        let data = example_data(20);
+        let name = "transaction";
+        let store = || FjallDbStore::new(LazyLock::force(&FJALL_DB), name).unwrap();
+        let mut tree = MerkleTree::<_>::construct(&data[..5], store());
+        let metadata = tree.metadata();
+        let failed = tree.transaction(|tx| {
+            tx.try_append_batch(&data[5..])?;
+            tx.try_update_leaf(30, &vec![130])
+        });
+        assert!(failed.is_err());
+        drop(tree);
+        let mut tree = MerkleTree::<_>::open(store()).unwrap();
+        assert_eq!(tree.metadata(), metadata);
+        assert_eq!(tree.prove(&data[5]), None);
+
+        tree.transaction(|tx| {
+            for leaf in &data[5..9] {
+                tx.try_append(leaf)?;
+            }
+            tx.try_append_batch(&data[9..])?;
+            tx.try_update_leaf(3, &vec![103])
+        })
+        .unwrap();
+        let root = *tree.root();
+        drop(tree);
+
+        let tree = MerkleTree::<_>::open(store()).unwrap();
+        assert_eq!(tree.root(), &root);
+        let mut input = data.clone();
+        input[3] = vec![103];
+        let expected = MerkleTree::<_>::construct(
+            &input,
+            FjallDbStore::new(LazyLock::force(&FJALL_DB), "transaction-expected").unwrap(),
+        );
+        assert_eq!(tree.root(), expected.root());
+        assert_eq!(tree.prove(&data[3]), None);
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = tree.prove(leaf).unwrap();
+            assert!(MerkleTree::<FjallDbStore>::verify_proof_at(
+                i, 32, leaf, &proof, &root
+            ));
+        }
+        // every committed node can be found by its hash
+        for (_, node) in tree.tree_cache.entries() {
+            let key = tree.tree_cache.get_key_by_hash(&node.data).unwrap();
+            assert_eq!(
+                tree.tree_cache.get(&key).map(|found| found.data),
+                Some(node.data)
+            );
+        }
+    }
 }
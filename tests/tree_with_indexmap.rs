@@ -4,7 +4,8 @@ mod tree_with_indexmap_store {
     use merkle_tree::{
         example_data,
         hashers::{GlobalHasher, Hasher},
-        HashDirection, MerkleTree, Node, PathTrace,
+        AbsenceProof, HashDirection, IntegrityReport, MerkleTree, MultiProof, Node, NodeStore,
+        PathTrace, RootBuilder, TreeLayout,
     };
     #[test]
     #[cfg(feature = "sha2")]
@@ -170,4 +171,1233 @@ mod tree_with_indexmap_store {
             ));
         }
     }
+    #[test]
+    #[allow(deprecated)]
+    fn stores_shift_the_root_to_the_left() {
+        let mut store = IndexMap::new();
+        let root = Node::new::<GlobalHasher>(&vec![1], false);
+        NodeStore::set(&mut store, PathTrace::root(-1), root);
+        store.shift_root_to_left(-1);
+        let left = PathTrace::new(HashDirection::Left, -1, 0);
+        assert_eq!(NodeStore::get(&store, &left), Some(root));
+        assert!(!NodeStore::exists(&store, &PathTrace::root(-1)));
+    }
+    #[test]
+    #[allow(deprecated)]
+    fn expanding_matches_append() {
+        let data = example_data(6);
+        for (size, padded) in [(4, false), (5, true)] {
+            let mut expanded = MerkleTree::<_>::construct(&data[..size], IndexMap::new());
+            let mut appended = MerkleTree::<_>::construct(&data[..size], IndexMap::new());
+            match padded {
+                true => expanded.expand_padded(&data[size]),
+                false => expanded.expand_tree(&data[size]),
+            }
+            appended.append(&data[size]);
+            assert_eq!(expanded.metadata(), appended.metadata());
+        }
+    }
+    #[test]
+    #[cfg(feature = "sha2")]
+    fn explicit_hasher_matches_default() {
+        use merkle_tree::hashers::Sha2Hasher;
+        let data = example_data(7);
+        let default_tree = MerkleTree::construct(&data, IndexMap::new());
+        let sha2_tree = MerkleTree::<_, Sha2Hasher>::construct_with_hasher(&data, IndexMap::new());
+        assert_eq!(default_tree.root(), sha2_tree.root());
+        let proof = sha2_tree.prove(&data[3]).unwrap();
+        assert!(MerkleTree::<IndexMap<_, _>, Sha2Hasher>::verify_proof(
+            &data[3],
+            &proof,
+            sha2_tree.root()
+        ));
+    }
+    #[test]
+    #[cfg(all(feature = "sha2", feature = "blake3"))]
+    fn trees_with_different_hashers_coexist() {
+        use merkle_tree::hashers::{Blake3, Sha2Hasher};
+        let data = example_data(8);
+        let sha2_tree = MerkleTree::<_, Sha2Hasher>::construct_with_hasher(&data, IndexMap::new());
+        let blake3_tree = MerkleTree::<_, Blake3>::construct_with_hasher(&data, IndexMap::new());
+        assert_ne!(sha2_tree.root(), blake3_tree.root());
+        for item in data.iter() {
+            let proof = blake3_tree.prove(item).unwrap();
+            assert!(MerkleTree::<IndexMap<_, _>, Blake3>::verify_proof(
+                item,
+                &proof,
+                blake3_tree.root()
+            ));
+            assert!(!MerkleTree::<IndexMap<_, _>, Sha2Hasher>::verify_proof(
+                item,
+                &proof,
+                blake3_tree.root()
+            ));
+        }
+        assert!(MerkleTree::<_, Blake3>::verify_with_hasher(
+            &data,
+            blake3_tree.root(),
+            IndexMap::new()
+        ));
+    }
+    /// the leaves used by the certificate-transparency test vectors
+    #[cfg(feature = "sha2")]
+    fn rfc6962_leaves() -> Vec<Vec<u8>> {
+        [
+            "",
+            "00",
+            "10",
+            "2021",
+            "3031",
+            "40414243",
+            "5051525354555657",
+            "606162636465666768696a6b6c6d6e6f",
+        ]
+        .iter()
+        .map(|leaf| hex::decode(leaf).unwrap())
+        .collect()
+    }
+    #[test]
+    #[cfg(feature = "sha2")]
+    fn rfc6962_roots_match_certificate_transparency() {
+        use merkle_tree::hashers::{Rfc6962, Sha2Hasher};
+        let leaves = rfc6962_leaves();
+        let expected = [
+            (
+                1,
+                "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            ),
+            (
+                2,
+                "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            ),
+            (
+                4,
+                "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            ),
+            (
+                8,
+                "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+            ),
+        ];
+        for (size, root) in expected {
+            let tree = MerkleTree::<_, Rfc6962<Sha2Hasher>>::construct_with_hasher(
+                &leaves[..size],
+                IndexMap::new(),
+            );
+            assert_eq!(hex::encode(tree.root()), root);
+        }
+        let tree =
+            MerkleTree::<_, Rfc6962<Sha2Hasher>>::construct_with_hasher(&leaves, IndexMap::new());
+        for leaf in leaves.iter() {
+            let proof = tree.prove(leaf).unwrap();
+            assert!(
+                MerkleTree::<IndexMap<_, _>, Rfc6962<Sha2Hasher>>::verify_proof(
+                    leaf,
+                    &proof,
+                    tree.root()
+                )
+            );
+            // a plain hasher can't reproduce the root from the same proof
+            assert!(!MerkleTree::<IndexMap<_, _>>::verify_proof(
+                leaf,
+                &proof,
+                tree.root()
+            ));
+        }
+    }
+    #[test]
+    fn rfc6962_interior_nodes_are_not_leaves() {
+        use merkle_tree::hashers::Rfc6962;
+        let data = example_data(4);
+        // a tree whose leaves are the concatenated leaf hashes of the level below
+        let forged: Vec<Vec<u8>> = data
+            .chunks(2)
+            .map(|pair| {
+                [
+                    GlobalHasher::hash_data(&pair[0]),
+                    GlobalHasher::hash_data(&pair[1]),
+                ]
+                .concat()
+            })
+            .collect();
+        let tree = MerkleTree::construct(&data, IndexMap::new());
+        let forged_tree = MerkleTree::construct(&forged, IndexMap::new());
+        assert_eq!(tree.root(), forged_tree.root());
+
+        let tree = MerkleTree::<_, Rfc6962>::construct_with_hasher(&data, IndexMap::new());
+        let forged: Vec<Vec<u8>> = data
+            .chunks(2)
+            .map(|pair| {
+                [
+                    Rfc6962::<GlobalHasher>::hash_leaf(&pair[0]),
+                    Rfc6962::<GlobalHasher>::hash_leaf(&pair[1]),
+                ]
+                .concat()
+            })
+            .collect();
+        let forged_tree = MerkleTree::<_, Rfc6962>::construct_with_hasher(&forged, IndexMap::new());
+        assert_ne!(tree.root(), forged_tree.root());
+    }
+    #[test]
+    #[cfg(feature = "sha2")]
+    fn rfc6962_unbalanced_roots_match_certificate_transparency() {
+        use merkle_tree::hashers::{Rfc6962, Sha2Hasher};
+        let leaves = rfc6962_leaves();
+        let expected = [
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        for (size, root) in (1..=leaves.len()).zip(expected) {
+            let tree = MerkleTree::<_, Rfc6962<Sha2Hasher>>::construct_with_layout(
+                &leaves[..size],
+                TreeLayout::Unbalanced,
+                IndexMap::new(),
+            );
+            assert_eq!(hex::encode(tree.root()), root);
+        }
+    }
+    #[test]
+    fn unbalanced_layout_has_no_duplicate_leaf_ambiguity() {
+        let data = example_data(3);
+        let mut duplicated = data.clone();
+        duplicated.push(data[2].clone());
+        let padded = MerkleTree::construct(&data, IndexMap::new());
+        let padded_duplicate = MerkleTree::construct(&duplicated, IndexMap::new());
+        assert_eq!(padded.root(), padded_duplicate.root());
+
+        let tree =
+            MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, IndexMap::new());
+        let duplicate_tree = MerkleTree::<_>::construct_with_layout(
+            &duplicated,
+            TreeLayout::Unbalanced,
+            IndexMap::new(),
+        );
+        assert_ne!(tree.root(), duplicate_tree.root());
+        assert_eq!(tree.leaf_count, 3);
+    }
+    #[test]
+    fn unbalanced_append_matches_construction() {
+        (2_usize..40).for_each(|size| {
+            let data = example_data(size);
+            let mut tree = MerkleTree::<_>::construct_with_layout(
+                &data,
+                TreeLayout::Unbalanced,
+                IndexMap::new(),
+            );
+            let extra: Vec<_> = (112..130).map(|d| vec![d]).collect();
+            let mut all = data.clone();
+            for item in extra.iter() {
+                tree.append(item);
+                all.push(item.clone());
+                let expected = MerkleTree::<_>::construct_with_layout(
+                    &all,
+                    TreeLayout::Unbalanced,
+                    IndexMap::new(),
+                );
+                assert_eq!(tree.root(), expected.root());
+                let proof = tree.prove(item).unwrap();
+                assert!(MerkleTree::<IndexMap<_, _>>::verify_proof(
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+            for item in all.iter() {
+                let proof = tree.prove(item).unwrap();
+                assert!(MerkleTree::<IndexMap<_, _>>::verify_proof(
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+        });
+    }
+    #[test]
+    fn unbalanced_parallel_construction_matches_append() {
+        let size = (1 << 14) + 3;
+        let data: Vec<_> = (0..size as u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let tree =
+            MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, IndexMap::new());
+        let mut appended = MerkleTree::<_>::construct_with_layout(
+            &data[..1 << 14],
+            TreeLayout::Unbalanced,
+            IndexMap::new(),
+        );
+        for item in data[1 << 14..].iter() {
+            appended.append(item);
+        }
+        assert_eq!(tree.root(), appended.root());
+        let proof = tree.prove(&data[size - 1]).unwrap();
+        assert!(MerkleTree::<IndexMap<_, _>>::verify_proof(
+            &data[size - 1],
+            &proof,
+            tree.root()
+        ));
+    }
+    #[test]
+    fn consistency_proofs_between_sizes() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data = example_data(40);
+        for new_size in 1..=data.len() {
+            let new_tree = MerkleTree::<_>::construct_with_layout(
+                &data[..new_size],
+                TreeLayout::Unbalanced,
+                IndexMap::new(),
+            );
+            for old_size in 1..=new_size {
+                let old_tree = MerkleTree::<_>::construct_with_layout(
+                    &data[..old_size],
+                    TreeLayout::Unbalanced,
+                    IndexMap::new(),
+                );
+                let proof = new_tree.prove_consistency(old_size).unwrap();
+                assert!(Tree::verify_consistency(
+                    old_tree.root(),
+                    new_tree.root(),
+                    old_size,
+                    new_size,
+                    &proof
+                ));
+                // a rewritten history is rejected
+                let forged = MerkleTree::<_>::construct_with_layout(
+                    &example_data(old_size + 1)[1..],
+                    TreeLayout::Unbalanced,
+                    IndexMap::new(),
+                );
+                assert!(!Tree::verify_consistency(
+                    forged.root(),
+                    new_tree.root(),
+                    old_size,
+                    new_size,
+                    &proof
+                ));
+                if let Some(first) = proof.hashes.first() {
+                    let mut tampered = proof.clone();
+                    tampered.hashes[0] = GlobalHasher::hash_data(first);
+                    assert!(!Tree::verify_consistency(
+                        old_tree.root(),
+                        new_tree.root(),
+                        old_size,
+                        new_size,
+                        &tampered
+                    ));
+                }
+            }
+            assert!(new_tree.prove_consistency(0).is_none());
+            assert!(new_tree.prove_consistency(new_size + 1).is_none());
+        }
+    }
+    #[test]
+    fn consistency_proofs_after_appends() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data = example_data(3);
+        let mut tree =
+            MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, IndexMap::new());
+        let mut roots = vec![(3, *tree.root())];
+        for item in (112..140).map(|d| vec![d]) {
+            tree.append(&item);
+            roots.push((tree.leaf_count, *tree.root()));
+        }
+        for (old_size, old_root) in roots {
+            let proof = tree.prove_consistency(old_size).unwrap();
+            assert!(Tree::verify_consistency(
+                &old_root,
+                tree.root(),
+                old_size,
+                tree.leaf_count,
+                &proof
+            ));
+        }
+    }
+    #[test]
+    fn padded_trees_have_no_consistency_proofs() {
+        let tree = MerkleTree::construct(example_data(5), IndexMap::new());
+        assert!(tree.prove_consistency(3).is_none());
+    }
+    #[test]
+    fn multiproofs_share_sibling_hashes() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for size in [1_usize, 2, 3, 7, 8, 13, 100] {
+                let data = example_data(size);
+                let tree = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+                let subsets: [Vec<_>; 3] = [
+                    data.clone(),
+                    data.iter().step_by(3).cloned().collect(),
+                    data.iter().rev().take(2).cloned().collect(),
+                ];
+                for subset in subsets {
+                    let proof = tree.multiproof(&subset).unwrap();
+                    let leaf_count = tree.metadata().leaf_count;
+                    assert!(Tree::verify_multiproof(
+                        leaf_count,
+                        &subset,
+                        &proof,
+                        tree.root()
+                    ));
+                    let separate: usize = tree
+                        .proof_multiple(&subset)
+                        .iter()
+                        .map(|proof| proof.hashes.len())
+                        .sum();
+                    assert!(proof.hashes.len() <= separate);
+                    // the data has to match the proven leaves
+                    let mut reordered = subset.clone();
+                    reordered.rotate_left(1);
+                    if reordered != subset {
+                        assert!(!Tree::verify_multiproof(
+                            leaf_count,
+                            &reordered,
+                            &proof,
+                            tree.root()
+                        ));
+                    }
+                    if let Some(hash) = proof.hashes.first().copied() {
+                        let mut tampered = proof.clone();
+                        tampered.hashes[0] = GlobalHasher::hash_data(&hash);
+                        assert!(!Tree::verify_multiproof(
+                            leaf_count,
+                            &subset,
+                            &tampered,
+                            tree.root()
+                        ));
+                        let mut extra = proof.clone();
+                        extra.hashes.push(hash);
+                        assert!(!Tree::verify_multiproof(
+                            leaf_count,
+                            &subset,
+                            &extra,
+                            tree.root()
+                        ));
+                        // every sibling the layout requires has to be there
+                        let mut missing = proof.clone();
+                        missing.hashes.pop();
+                        assert!(!Tree::verify_multiproof(
+                            leaf_count,
+                            &subset,
+                            &missing,
+                            tree.root()
+                        ));
+                    }
+                }
+            }
+        }
+        let data: Vec<_> = (0..1024_u32).map(|i| i.to_be_bytes().to_vec()).collect();
+        let tree = MerkleTree::construct(&data, IndexMap::new());
+        let proof = tree.multiproof(&data[..512]).unwrap();
+        // a complete subtree only needs the root of its sibling subtree
+        assert_eq!(proof.hashes.len(), 1);
+        assert!(tree.multiproof(&[vec![255, 255]]).is_none());
+    }
+    #[test]
+    fn multiproofs_need_the_trusted_leaf_count() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data = example_data(4);
+        let tree = MerkleTree::<_>::construct(&data, IndexMap::new());
+        let leaf = |i: usize| GlobalHasher::hash_leaf(&data[i]);
+        // the left child of the root passed off as a leaf, by the 64 bytes of its children
+        let forged_leaf = [leaf(0), leaf(1)].concat();
+        let forged = MultiProof {
+            leaf_indexes: vec![0],
+            hashes: vec![GlobalHasher::hash_concat(&leaf(2), &leaf(3))],
+        };
+        assert!(!Tree::verify_multiproof(
+            4,
+            &[&forged_leaf],
+            &forged,
+            tree.root()
+        ));
+        let proof = tree.multiproof(&data[..1]).unwrap();
+        assert_eq!(proof.leaf_indexes, vec![0]);
+        assert!(Tree::verify_multiproof(4, &data[..1], &proof, tree.root()));
+        assert!(!Tree::verify_multiproof(2, &data[..1], &proof, tree.root()));
+        assert!(!Tree::verify_multiproof(8, &data[..1], &proof, tree.root()));
+    }
+    #[test]
+    fn index_proofs_with_repeated_data() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data: Vec<_> = [1_u8, 2, 1, 1, 3, 2, 1].iter().map(|d| vec![*d]).collect();
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let tree = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+            for (index, item) in data.iter().enumerate() {
+                let proof = tree.prove_index(index).unwrap();
+                assert_eq!(proof.leaf_index, index);
+                assert_eq!(proof.leaf_count, tree.leaf_count);
+                assert!(Tree::verify_proof_at(
+                    index,
+                    tree.leaf_count,
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+                assert!(!Tree::verify_proof_at(
+                    index + 1,
+                    tree.leaf_count,
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+                assert!(!Tree::verify_proof_at(
+                    index,
+                    tree.leaf_count + 1,
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+            // the padding of the padded tree isn't proven
+            assert!(tree.prove_index(tree.len()).is_none());
+        }
+        // the last leaf is promoted in the unbalanced layout
+        let tree =
+            MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, IndexMap::new());
+        let proof = tree.prove(&data[6]).unwrap();
+        assert_eq!(proof.leaf_index, 6);
+        assert_eq!(proof, tree.prove_index(6).unwrap());
+    }
+    #[test]
+    fn proofs_bind_the_leaf_position() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for size in 1_usize..40 {
+                let data = example_data(size);
+                let tree = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+                let count = tree.leaf_count;
+                for (index, item) in data.iter().enumerate() {
+                    let proof = tree.prove_index(index).unwrap();
+                    let hashes = proof.sibling_hashes();
+                    assert!(Tree::verify_hashes_at(
+                        index,
+                        count,
+                        item,
+                        &hashes,
+                        tree.root()
+                    ));
+                    assert!(Tree::verify_proof_at(
+                        index,
+                        count,
+                        item,
+                        &proof,
+                        tree.root()
+                    ));
+                    // a claimed position the leaf doesn't hold
+                    let other = (index + 1) % count;
+                    // (padding repeats the last leaf so those positions hold it too)
+                    if other != index && other < size {
+                        let mut moved = proof.clone();
+                        moved.leaf_index = other;
+                        assert!(!Tree::verify_proof_at(
+                            other,
+                            count,
+                            item,
+                            &moved,
+                            tree.root()
+                        ));
+                        assert!(!Tree::verify_hashes_at(
+                            other,
+                            count,
+                            item,
+                            &hashes,
+                            tree.root()
+                        ));
+                    }
+                    // directions that disagree with the position
+                    if let Some((level, direction, hash)) = proof.hashes.first().copied() {
+                        let mut flipped = proof.clone();
+                        flipped.hashes[0] = (level, direction.reverse(), hash);
+                        assert!(!Tree::verify_proof_at(
+                            index,
+                            count,
+                            item,
+                            &flipped,
+                            tree.root()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    #[test]
+    fn proof_binary_encoding_round_trips() {
+        use merkle_tree::{hashers::Rfc6962, Proof, ProofEncodingError, PROOF_FORMAT_VERSION};
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>, Rfc6962>;
+        let data = example_data(11);
+        let tree = MerkleTree::<_, Rfc6962>::construct_with_layout(
+            &data,
+            TreeLayout::Unbalanced,
+            IndexMap::new(),
+        );
+        let proof = tree.prove_index(5).unwrap();
+        assert_eq!(proof.root, *tree.root());
+        assert_eq!(proof.algorithm, <Rfc6962 as Hasher>::ALGORITHM);
+        assert!(proof.domain_separated);
+
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes[0], PROOF_FORMAT_VERSION);
+        let decoded = Proof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(Tree::verify_proof_at(
+            5,
+            11,
+            &data[5],
+            &decoded,
+            tree.root()
+        ));
+        // a proof from a hasher without domain separation is rejected
+        assert!(!MerkleTree::<IndexMap<PathTrace, Node>>::verify_proof_at(
+            5,
+            11,
+            &data[5],
+            &decoded,
+            tree.root()
+        ));
+
+        assert_eq!(Proof::from_bytes(&[]), Err(ProofEncodingError::Truncated));
+        assert_eq!(
+            Proof::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ProofEncodingError::Truncated)
+        );
+        let mut trailing = bytes.clone();
+        trailing.extend([0, 0]);
+        assert_eq!(
+            Proof::from_bytes(&trailing),
+            Err(ProofEncodingError::TrailingBytes(2))
+        );
+        let mut version = bytes.clone();
+        version[0] = PROOF_FORMAT_VERSION + 1;
+        assert_eq!(
+            Proof::from_bytes(&version),
+            Err(ProofEncodingError::UnsupportedVersion(
+                PROOF_FORMAT_VERSION + 1
+            ))
+        );
+        let mut algorithm = bytes.clone();
+        algorithm[1] = 200;
+        assert!(matches!(
+            Proof::from_bytes(&algorithm),
+            Err(ProofEncodingError::UnknownAlgorithm(_))
+        ));
+        // the number of hashes follows from the leaf count
+        let leaf_count = |count: u64| {
+            let mut bytes = bytes.clone();
+            bytes[3 + 8..3 + 8 + 8].copy_from_slice(&count.to_le_bytes());
+            Proof::from_bytes(&bytes)
+        };
+        assert_eq!(leaf_count(1 << 20), Err(ProofEncodingError::Truncated));
+        assert_eq!(
+            leaf_count(1),
+            Err(ProofEncodingError::TrailingBytes(4 * 32))
+        );
+
+        // the levels are rebuilt from the root at level 0, whichever level the root of the tree is at
+        let mut tree = MerkleTree::<_, Rfc6962>::construct_with_layout(
+            &data[..3],
+            TreeLayout::Padded,
+            IndexMap::new(),
+        );
+        tree.append_batch(&data[3..]);
+        let proof = tree.prove_index(5).unwrap();
+        let decoded = Proof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(decoded.sibling_hashes(), proof.sibling_hashes());
+        assert!(Tree::verify_proof_at(
+            5,
+            16,
+            &data[5],
+            &decoded,
+            tree.root()
+        ));
+    }
+    #[test]
+    #[cfg(feature = "json")]
+    fn proof_json_encoding_round_trips() {
+        use merkle_tree::{Proof, ProofEncodingError};
+        let data = example_data(6);
+        let tree = MerkleTree::construct(&data, IndexMap::new());
+        let proof = tree.prove_index(2).unwrap();
+        let json = proof.to_json();
+        assert!(json.contains(&hex::encode(tree.root())));
+        assert_eq!(Proof::from_json(&json).unwrap(), proof);
+        assert!(matches!(
+            Proof::from_json("{"),
+            Err(ProofEncodingError::InvalidJson(_))
+        ));
+        let root = hex::encode(tree.root());
+        assert!(matches!(
+            Proof::from_json(&json.replace(&root, "zz")),
+            Err(ProofEncodingError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Proof::from_json(&json.replace("\"right\"", "\"up\"")),
+            Err(ProofEncodingError::UnknownDirection(_))
+        ));
+    }
+    #[test]
+    fn invalid_input_is_an_error() {
+        use merkle_tree::MerkleError;
+        assert!(matches!(
+            MerkleTree::try_from_iter(example_data(3).into_iter(), 5, IndexMap::new()),
+            Err(MerkleError::InputShorterThanSizeHint(5))
+        ));
+        let mut tree = MerkleTree::try_construct(example_data(5), IndexMap::new()).unwrap();
+        let missing = GlobalHasher::hash_leaf(&vec![200]);
+        assert!(matches!(
+            tree.try_update(&missing, missing),
+            Err(MerkleError::HashNotFound(hash)) if hash == missing
+        ));
+        let root = *tree.root();
+        // the infallible update ignores missing hashes
+        tree.update(&missing, missing);
+        assert_eq!(tree.root(), &root);
+        tree.try_append(&vec![9]).unwrap();
+        assert_eq!(
+            tree.root(),
+            MerkleTree::construct(
+                example_data(5).into_iter().chain([vec![9]]),
+                IndexMap::new()
+            )
+            .root()
+        );
+    }
+    #[test]
+    fn metadata_round_trips() {
+        use merkle_tree::{MerkleError, TreeMetadata};
+        let tree = MerkleTree::<_>::construct_with_layout(
+            example_data(5),
+            TreeLayout::Unbalanced,
+            IndexMap::new(),
+        );
+        let metadata = tree.metadata();
+        assert_eq!(
+            TreeMetadata::from_bytes(&metadata.to_bytes()).unwrap(),
+            metadata
+        );
+        assert!(matches!(
+            TreeMetadata::from_bytes(&metadata.to_bytes()[1..]),
+            Err(MerkleError::InvalidMetadata(_))
+        ));
+        // in-memory stores don't persist anything to reopen
+        assert!(matches!(
+            MerkleTree::open(IndexMap::new()),
+            Err(MerkleError::MissingMetadata)
+        ));
+    }
+    /// a store whose batch writes start failing, like a disk that filled up
+    #[derive(Debug, Default)]
+    struct FailingStore {
+        nodes: IndexMap<PathTrace, Node>,
+        full: bool,
+    }
+    impl merkle_tree::NodeStore for FailingStore {
+        fn set(&mut self, key: PathTrace, value: Node) -> Option<Node> {
+            self.nodes.set(key, value)
+        }
+        fn get(&self, key: &PathTrace) -> Option<Node> {
+            merkle_tree::NodeStore::get(&self.nodes, key)
+        }
+        fn get_key_by_hash(&self, hash: &merkle_tree::Hash) -> Option<PathTrace> {
+            self.nodes.get_key_by_hash(hash)
+        }
+        fn sort(&mut self) {
+            merkle_tree::NodeStore::sort(&mut self.nodes)
+        }
+        fn exists(&self, key: &PathTrace) -> bool {
+            self.nodes.exists(key)
+        }
+        fn reserve(&mut self, items: usize) {
+            merkle_tree::NodeStore::reserve(&mut self.nodes, items)
+        }
+        fn update_value(&mut self, key: &PathTrace, next_value: Node) {
+            self.nodes.update_value(key, next_value)
+        }
+        fn entries(&self) -> impl Iterator<Item = (PathTrace, Node)> {
+            self.nodes.entries()
+        }
+        fn trigger_batch_actions(&mut self) {}
+        fn remove_node(&mut self, key: PathTrace) {
+            self.nodes.remove_node(key)
+        }
+        fn try_trigger_batch_actions(&mut self) -> Result<(), merkle_tree::MerkleError> {
+            if self.full {
+                return Err(merkle_tree::MerkleError::store("no space left on device"));
+            }
+            Ok(())
+        }
+    }
+    #[test]
+    fn store_failures_are_returned() {
+        use merkle_tree::MerkleError;
+        let store = FailingStore {
+            full: true,
+            ..Default::default()
+        };
+        let error = MerkleTree::try_construct(example_data(4), store).unwrap_err();
+        assert!(matches!(error, MerkleError::Store(_)));
+        assert_eq!(
+            error.to_string(),
+            "node store error: no space left on device"
+        );
+
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let mut tree = MerkleTree::<_>::try_construct_with_layout(
+                example_data(4),
+                layout,
+                FailingStore::default(),
+            )
+            .unwrap();
+            tree.tree_cache.full = true;
+            assert!(matches!(
+                tree.try_append(&vec![9]),
+                Err(MerkleError::Store(_))
+            ));
+        }
+    }
+    #[test]
+    fn empty_and_single_leaf_trees() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let empty: Vec<Vec<u8>> = vec![];
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let tree = MerkleTree::<_>::construct_with_layout(&empty, layout, IndexMap::new());
+            assert!(tree.is_empty());
+            assert_eq!(tree.root(), &Tree::empty_root());
+            assert_eq!(Tree::empty_root(), GlobalHasher::hash_data(&[]));
+            assert!(tree.prove_index(0).is_none());
+
+            let single = [vec![7]];
+            let tree = MerkleTree::<_>::construct_with_layout(&single, layout, IndexMap::new());
+            assert_eq!(tree.leaf_count, 1);
+            assert_eq!(tree.root(), &GlobalHasher::hash_leaf(&single[0]));
+            let proof = tree.prove(&single[0]).unwrap();
+            assert!(proof.hashes.is_empty());
+            assert!(Tree::verify_proof_at(0, 1, &single[0], &proof, tree.root()));
+        }
+        #[cfg(feature = "sha2")]
+        assert_eq!(
+            hex::encode(MerkleTree::construct(&empty, IndexMap::new()).root()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+    #[test]
+    fn append_from_an_empty_tree() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let empty: Vec<Vec<u8>> = vec![];
+        let data = example_data(20);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let mut tree = MerkleTree::<_>::construct_with_layout(&empty, layout, IndexMap::new());
+            for (count, item) in data.iter().enumerate() {
+                tree.append(item);
+                let expected = MerkleTree::<_>::construct_with_layout(
+                    &data[..=count],
+                    layout,
+                    IndexMap::new(),
+                );
+                assert_eq!(tree.root(), expected.root(), "{layout:?} {count}");
+                for item in data[..=count].iter() {
+                    let proof = tree.prove(item).unwrap();
+                    assert!(Tree::verify_proof(item, &proof, tree.root()));
+                }
+            }
+        }
+    }
+    #[test]
+    fn padded_append_matches_construction() {
+        let data = example_data(40);
+        for start in 1..10 {
+            let mut tree = MerkleTree::construct(&data[..start], IndexMap::new());
+            for count in start..data.len() {
+                tree.append(&data[count]);
+                let expected = MerkleTree::construct(&data[..=count], IndexMap::new());
+                assert_eq!(tree.root(), expected.root(), "{start} {count}");
+            }
+        }
+    }
+    #[test]
+    fn truncate_matches_construction() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data = example_data(18);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for size in 1..data.len() {
+                for new_len in 0..=size {
+                    let mut tree = MerkleTree::<_>::construct_with_layout(
+                        &data[..size],
+                        layout,
+                        IndexMap::new(),
+                    );
+                    tree.truncate(new_len);
+                    let expected = MerkleTree::<_>::construct_with_layout(
+                        &data[..new_len],
+                        layout,
+                        IndexMap::new(),
+                    );
+                    assert_eq!(tree.root(), expected.root(), "{layout:?} {size} {new_len}");
+                    assert_eq!(tree.len(), new_len);
+                    assert_eq!(tree.leaf_count, expected.leaf_count);
+                    for (index, item) in data[..new_len].iter().enumerate() {
+                        let proof = tree.prove_index(index).unwrap();
+                        assert!(Tree::verify_proof_at(
+                            index,
+                            tree.leaf_count,
+                            item,
+                            &proof,
+                            tree.root()
+                        ));
+                    }
+                    assert!(tree.prove(&data[size - 1]).is_none() || new_len == size);
+                    // the truncated tree keeps growing like a constructed one
+                    tree.append(&data[size - 1]);
+                    let mut input = data[..new_len].to_vec();
+                    input.push(data[size - 1].clone());
+                    let expected =
+                        MerkleTree::<_>::construct_with_layout(&input, layout, IndexMap::new());
+                    assert_eq!(tree.root(), expected.root(), "{layout:?} {size} {new_len}");
+                }
+            }
+        }
+    }
+    #[test]
+    fn remove_leaves() {
+        use merkle_tree::{MerkleError, RemovalMode};
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data = example_data(11);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for index in 0..data.len() {
+                let mut tree =
+                    MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+                tree.remove(index, RemovalMode::Compact);
+                let mut input = data.clone();
+                input.remove(index);
+                let expected =
+                    MerkleTree::<_>::construct_with_layout(&input, layout, IndexMap::new());
+                assert_eq!(tree.root(), expected.root(), "{layout:?} {index}");
+                assert_eq!(tree.len(), input.len());
+
+                let mut tree =
+                    MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+                tree.remove(index, RemovalMode::Tombstone);
+                assert_eq!(tree.len(), data.len());
+                assert!(tree.prove(&data[index]).is_none());
+                for (position, item) in data.iter().enumerate().filter(|(i, _)| *i != index) {
+                    let proof = tree.prove(item).unwrap();
+                    assert!(Tree::verify_proof(item, &proof, tree.root()));
+                    assert_eq!(proof.leaf_index, position);
+                }
+            }
+            let mut tree = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+            assert!(matches!(
+                tree.try_remove(data.len(), RemovalMode::Compact),
+                Err(MerkleError::LeafIndexOutOfRange(11))
+            ));
+        }
+    }
+    #[test]
+    fn append_batch_matches_construction() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let data = example_data(40);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for start in 0..10 {
+                for end in start..data.len() {
+                    let mut tree = MerkleTree::<_>::construct_with_layout(
+                        &data[..start],
+                        layout,
+                        IndexMap::new(),
+                    );
+                    tree.append_batch(&data[start..end]);
+                    let expected = MerkleTree::<_>::construct_with_layout(
+                        &data[..end],
+                        layout,
+                        IndexMap::new(),
+                    );
+                    assert_eq!(tree.root(), expected.root(), "{layout:?} {start} {end}");
+                    assert_eq!(tree.len(), end);
+                    for item in data[..end].iter() {
+                        let proof = tree.prove(item).unwrap();
+                        assert!(Tree::verify_proof(item, &proof, tree.root()));
+                    }
+                    // and keeps growing like a constructed tree
+                    tree.append(&data[end]);
+                    let expected = MerkleTree::<_>::construct_with_layout(
+                        &data[..=end],
+                        layout,
+                        IndexMap::new(),
+                    );
+                    assert_eq!(tree.root(), expected.root(), "{layout:?} {start} {end}");
+                }
+            }
+        }
+    }
+    #[test]
+    fn update_many_matches_construction() {
+        use merkle_tree::MerkleError;
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for size in 1..20 {
+                let data = example_data(size);
+                let indexes = [0, size / 3, size / 2, size - 1];
+                let updates: Vec<_> = indexes.iter().map(|&i| (i, vec![100 + i as u8])).collect();
+                let mut tree =
+                    MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+                tree.update_many(updates.clone());
+                let mut input = data.clone();
+                for (index, item) in updates.iter() {
+                    input[*index] = item.clone();
+                }
+                let expected =
+                    MerkleTree::<_>::construct_with_layout(&input, layout, IndexMap::new());
+                assert_eq!(tree.root(), expected.root(), "{layout:?} {size}");
+                for (index, item) in input.iter().enumerate() {
+                    let proof = tree.prove_index(index).unwrap();
+                    assert!(Tree::verify_proof_at(
+                        index,
+                        tree.leaf_count,
+                        item,
+                        &proof,
+                        tree.root()
+                    ));
+                }
+                assert!(matches!(
+                    tree.try_update_many([(size, vec![0])]),
+                    Err(MerkleError::LeafIndexOutOfRange(index)) if index == size
+                ));
+            }
+        }
+        // no updates change nothing, also in an empty tree
+        let mut empty = MerkleTree::<_>::construct(Vec::<Vec<u8>>::new(), IndexMap::new());
+        empty
+            .try_update_many(Vec::<(usize, Vec<u8>)>::new())
+            .unwrap();
+        assert_eq!(empty.root(), &Tree::empty_root());
+        let mut tree = MerkleTree::<_>::construct(example_data(5), IndexMap::new());
+        let root = *tree.root();
+        tree.update_many(Vec::<(usize, Vec<u8>)>::new());
+        assert_eq!(tree.root(), &root);
+        assert!(matches!(
+            empty.try_update_many([(0, vec![0])]),
+            Err(MerkleError::LeafIndexOutOfRange(0))
+        ));
+    }
+    #[test]
+    fn update_leaves_by_index_and_data() {
+        let data = example_data(7);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let mut tree = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+            let old = tree.update_leaf(2, &vec![102]).unwrap();
+            assert_eq!(old, GlobalHasher::hash_leaf(&data[2]));
+            assert_eq!(tree.update_leaf(7, &vec![107]), None);
+            let old = tree.replace(&data[6], &vec![106]).unwrap();
+            assert_eq!(old, GlobalHasher::hash_leaf(&data[6]));
+            assert_eq!(tree.replace(&data[6], &vec![106]), None);
+
+            let mut input = data.clone();
+            input[2] = vec![102];
+            input[6] = vec![106];
+            let expected = MerkleTree::<_>::construct_with_layout(&input, layout, IndexMap::new());
+            assert_eq!(tree.root(), expected.root(), "{layout:?}");
+
+            // the data of an interior node, (the concatenation of its children) is not a leaf
+            let left = tree
+                .tree_cache
+                .get(&tree.path_at(tree.level_count(), 0))
+                .unwrap();
+            let right = tree
+                .tree_cache
+                .get(&tree.path_at(tree.level_count(), 1))
+                .unwrap();
+            let interior = [left.data, right.data].concat();
+            assert!(tree
+                .fetch_cache_pathtrace(&GlobalHasher::hash_leaf(&interior))
+                .is_some());
+            assert_eq!(tree.replace(&interior, &vec![200]), None);
+            assert_eq!(tree.root(), expected.root());
+        }
+    }
+    #[test]
+    fn absence_proofs_in_sorted_trees() {
+        // every other byte is in the tree, with duplicates
+        let present: Vec<Vec<u8>> = (0..40_u8).step_by(2).map(|i| vec![i]).collect();
+        let input = present.iter().chain(&present[..5]);
+        let tree = MerkleTree::<_>::construct_sorted(input, IndexMap::new());
+        assert_eq!(tree.len(), present.len());
+        for data in &present {
+            assert_eq!(tree.prove_absence(data), None);
+        }
+        let mut sides = (false, false);
+        for absent in (0..1000_u16).map(|i| i.to_be_bytes().to_vec()) {
+            let proof = tree.prove_absence(&absent).unwrap();
+            sides.0 |= proof.left.is_none();
+            sides.1 |= proof.right.is_none();
+            assert!(MerkleTree::<IndexMap<_, _>>::verify_absence(
+                tree.len(),
+                &absent,
+                &proof,
+                tree.root()
+            ));
+            for data in present.iter().step_by(7) {
+                assert!(!MerkleTree::<IndexMap<_, _>>::verify_absence(
+                    tree.len(),
+                    data,
+                    &proof,
+                    tree.root()
+                ));
+            }
+            // the bracketing leaves have to be adjacent
+            if let (Some(left), Some(_)) = (&proof.left, &proof.right) {
+                if left.1.leaf_index > 0 {
+                    let mut apart = proof.clone();
+                    let index = left.1.leaf_index - 1;
+                    let hash = tree
+                        .tree_cache
+                        .get(&tree.path_at(tree.level_count(), index))
+                        .unwrap()
+                        .data;
+                    apart.left = Some((hash, tree.prove_index(index).unwrap()));
+                    assert!(!MerkleTree::<IndexMap<_, _>>::verify_absence(
+                        tree.len(),
+                        &absent,
+                        &apart,
+                        tree.root()
+                    ));
+                }
+            }
+            // and both sides are needed
+            let one_sided = AbsenceProof {
+                left: None,
+                ..proof.clone()
+            };
+            assert!(
+                proof.left.is_none()
+                    || !MerkleTree::<IndexMap<_, _>>::verify_absence(
+                        tree.len(),
+                        &absent,
+                        &one_sided,
+                        tree.root()
+                    )
+            );
+        }
+        // keys before the first and after the last leaf hash are covered
+        assert_eq!(sides, (true, true));
+
+        let padded = MerkleTree::<_>::construct(&present, IndexMap::new());
+        assert_eq!(padded.prove_absence(&vec![1]), None);
+        let empty = MerkleTree::<_>::construct_sorted(Vec::<Vec<u8>>::new(), IndexMap::new());
+        let proof = empty.prove_absence(&vec![1]).unwrap();
+        assert!(MerkleTree::<IndexMap<_, _>>::verify_absence(
+            0,
+            &vec![1],
+            &proof,
+            empty.root()
+        ));
+    }
+    #[test]
+    fn absence_proofs_need_the_trusted_leaf_count() {
+        type Tree = MerkleTree<IndexMap<PathTrace, Node>>;
+        let mut forged = 0;
+        for seed in 0..40_u8 {
+            let present: Vec<Vec<u8>> = (0..8).map(|i| vec![seed, i]).collect();
+            let tree = Tree::construct_sorted(&present, IndexMap::new());
+            // the nodes above the leaves, passed off as the leaves of a tree with 4 leaves
+            let interior = |index: usize| {
+                let path = tree.path_at(tree.level_count() - 1, index);
+                let mut proof = tree.prove_index(index * 2).unwrap();
+                proof.hashes.remove(0);
+                proof.leaf_index = index;
+                proof.leaf_count = 4;
+                (tree.tree_cache.get(&path).unwrap().data, proof)
+            };
+            for index in 0..3 {
+                let proof = AbsenceProof {
+                    left: Some(interior(index)),
+                    right: Some(interior(index + 1)),
+                };
+                for data in &present {
+                    if Tree::verify_absence(4, data, &proof, tree.root()) {
+                        forged += 1;
+                        assert!(!Tree::verify_absence(tree.len(), data, &proof, tree.root()));
+                    }
+                }
+            }
+        }
+        // the forgery brackets members of the tree
+        assert!(forged > 0);
+    }
+    #[test]
+    fn streamed_roots_match_construction() {
+        for size in (0..70).chain([1000, 20_000]) {
+            let data = example_data(size);
+            for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+                let tree = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+                let root = RootBuilder::root_of_stream(&data, layout);
+                assert_eq!(&root, tree.root(), "{size} {layout:?}");
+            }
+        }
+        use merkle_tree::hashers::Rfc6962;
+        let data = example_data(13);
+        let mut builder = RootBuilder::<Rfc6962<GlobalHasher>>::with_hasher(TreeLayout::Unbalanced);
+        builder.extend(&data);
+        assert_eq!(builder.len(), 13);
+        let tree = MerkleTree::<_, Rfc6962<GlobalHasher>>::construct_with_layout(
+            &data,
+            TreeLayout::Unbalanced,
+            IndexMap::new(),
+        );
+        assert_eq!(&builder.finish(), tree.root());
+    }
+    #[test]
+    fn check_and_repair_integrity() {
+        let data = example_data(13);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let mut tree =
+                MerkleTree::<_>::construct_with_layout(&data[..3], layout, IndexMap::new());
+            tree.append_batch(&data[3..]);
+            assert!(tree.check_integrity().unwrap().is_ok());
+            let root = *tree.root();
+            let metadata = tree.metadata();
+
+            let interior = tree.path_at(metadata.level_count - 1, 1);
+            let corrupted = Node::new::<GlobalHasher>(&vec![200], false);
+            tree.tree_cache.insert(interior, corrupted);
+            let stray = PathTrace::new(HashDirection::Left, metadata.level_count + 1, 0);
+            tree.tree_cache.insert(stray, corrupted);
+            tree.tree_cache.sort();
+            let report = tree.check_integrity().unwrap();
+            // the parent of the corrupted node doesn't hash to its children either
+            let parent = interior.get_parent_path(metadata.lowest_level).unwrap();
+            assert_eq!(
+                report,
+                IntegrityReport {
+                    mismatched: vec![parent, interior],
+                    unexpected: vec![stray],
+                    ..IntegrityReport::default()
+                },
+                "{layout:?}"
+            );
+
+            tree.repair().unwrap();
+            assert!(tree.check_integrity().unwrap().is_ok(), "{layout:?}");
+            assert_eq!(tree.root(), &root);
+            assert_eq!(tree.metadata().leaf_count, metadata.leaf_count);
+            let expected = MerkleTree::<_>::construct_with_layout(&data, layout, IndexMap::new());
+            assert_eq!(tree.metadata(), expected.metadata());
+            for (i, leaf) in data.iter().enumerate() {
+                let proof = tree.prove(leaf).unwrap();
+                assert!(MerkleTree::<IndexMap<PathTrace, Node>>::verify_proof_at(
+                    i,
+                    metadata.leaf_count,
+                    leaf,
+                    &proof,
+                    &root
+                ));
+            }
+
+            // a missing leaf can't be rebuilt
+            let leaf = tree.path_at(tree.metadata().level_count, 4);
+            tree.tree_cache.shift_remove(&leaf);
+            assert_eq!(tree.check_integrity().unwrap().missing, vec![leaf]);
+            assert!(tree.repair().is_err());
+        }
+    }
 }
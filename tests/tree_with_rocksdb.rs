@@ -5,7 +5,7 @@ mod tree_with_rocksdb {
         example_data,
         hashers::{GlobalHasher, Hasher},
         stores::{temporary_rocks_db, NodeStore, RocksDb, RocksDbStore},
-        HashDirection, MerkleTree, PathTrace,
+        HashDirection, MerkleTree, PathTrace, TreeLayout,
     };
     use std::sync::LazyLock;
     static ROCKS_DB: LazyLock<RocksDb> = LazyLock::new(temporary_rocks_db);
@@ -195,4 +195,58 @@ mod tree_with_rocksdb {
             ));
         }
     }
+    #[test]
+    fn unbalanced_append_and_prove() {
+        (2_usize..20).for_each(|size| {
+            let data = example_data(size);
+            let store = RocksDbStore::new(
+                LazyLock::force(&ROCKS_DB),
+                &format!("unbalanced_append-{size}"),
+            )
+            .unwrap();
+            let mut tree =
+                MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, store);
+            let input: Vec<_> = (112..130).map(|d| vec![d]).collect();
+            for h in input.iter() {
+                tree.append(h);
+                let proof = tree.prove(h).unwrap();
+                assert!(MerkleTree::<RocksDbStore>::verify_proof(
+                    h,
+                    &proof,
+                    tree.root()
+                ))
+            }
+            assert_eq!(tree.leaf_count, size + input.len());
+        });
+    }
+    #[test]
+    fn reopen_a_persisted_tree() {
+        let data = example_data(11);
+        let extra = vec![100];
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let name = format!("reopen-{layout:?}");
+            let store = RocksDbStore::new(LazyLock::force(&ROCKS_DB), &name).unwrap();
+            let tree = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            let metadata = tree.metadata();
+            drop(tree);
+
+            let store = RocksDbStore::new(LazyLock::force(&ROCKS_DB), &name).unwrap();
+            let mut tree = MerkleTree::open(store).unwrap();
+            assert_eq!(tree.metadata(), metadata);
+            tree.append(&extra);
+
+            let mut input = data.clone();
+            input.push(extra.clone());
+            let store =
+                RocksDbStore::new(LazyLock::force(&ROCKS_DB), &format!("{name}-expected")).unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&input, layout, store);
+            assert_eq!(tree.root(), expected.root());
+            let proof = tree.prove(&extra).unwrap();
+            assert!(MerkleTree::<RocksDbStore>::verify_proof(
+                &extra,
+                &proof,
+                tree.root()
+            ));
+        }
+    }
 }
@@ -5,7 +5,7 @@ mod tree_with_sled_store {
         example_data,
         hashers::{GlobalHasher, Hasher},
         stores::{NodeStore, SledStore},
-        HashDirection, MerkleTree, PathTrace,
+        HashDirection, MerkleTree, PathTrace, TreeLayout,
     };
     use sled::{self, Config, Db, Mode};
     use std::sync::LazyLock;
@@ -193,4 +193,571 @@ mod tree_with_sled_store {
             ));
         }
     }
+    #[test]
+    fn unbalanced_append_and_prove() {
+        (2_usize..20).for_each(|size| {
+            let data = example_data(size);
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("unbalanced_append-{size}"),
+            )
+            .unwrap();
+            let mut tree =
+                MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, store);
+            let input: Vec<_> = (112..130).map(|d| vec![d]).collect();
+            for h in input.iter() {
+                tree.append(h);
+                let proof = tree.prove(h).unwrap();
+                assert!(MerkleTree::<SledStore>::verify_proof(
+                    h,
+                    &proof,
+                    tree.root()
+                ))
+            }
+            assert_eq!(tree.leaf_count, size + input.len());
+        });
+    }
+    #[test]
+    fn multiproof_after_appends() {
+        let data = example_data(10);
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "multiproof").unwrap();
+        let mut tree = MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, store);
+        let input: Vec<_> = (112..125).map(|d| vec![d]).collect();
+        for h in input.iter() {
+            tree.append(h);
+        }
+        let subset = [
+            data[1].clone(),
+            data[6].clone(),
+            input[4].clone(),
+            input[12].clone(),
+        ];
+        let proof = tree.multiproof(&subset).unwrap();
+        assert!(MerkleTree::<SledStore>::verify_multiproof(
+            tree.metadata().leaf_count,
+            &subset,
+            &proof,
+            tree.root()
+        ));
+    }
+    #[test]
+    fn index_proofs_with_repeated_data() {
+        let data: Vec<_> = [4_u8, 4, 4, 5, 4].iter().map(|d| vec![*d]).collect();
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "index_proofs").unwrap();
+        let tree = MerkleTree::<_>::construct_with_layout(&data, TreeLayout::Unbalanced, store);
+        for (index, item) in data.iter().enumerate() {
+            let proof = tree.prove_index(index).unwrap();
+            assert!(MerkleTree::<SledStore>::verify_proof_at(
+                index,
+                data.len(),
+                item,
+                &proof,
+                tree.root()
+            ));
+        }
+    }
+    #[test]
+    fn find_repeated_data_after_overwriting_a_copy() {
+        let data: Vec<_> = [1_u8, 2, 1, 3, 1, 4].iter().map(|d| vec![*d]).collect();
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let name = format!("repeated-{layout:?}");
+            let store = SledStore::new(LazyLock::force(&SLED_DB), &name).unwrap();
+            let mut tree = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            // the lookup of the repeated data points at the first copy, which goes away
+            tree.update_leaf(0, &vec![10]).unwrap();
+            let proof = tree.prove(&data[0]).unwrap();
+            assert!(proof.leaf_index == 2 || proof.leaf_index == 4);
+            let hash = GlobalHasher::hash_leaf(&data[0]);
+            tree.try_update(&hash, GlobalHasher::hash_leaf(&vec![11]))
+                .unwrap();
+            tree.replace(&data[0], &vec![12]).unwrap();
+            assert!(tree.prove(&data[0]).is_none());
+            assert!(tree.replace(&data[0], &vec![13]).is_none());
+            tree.append_batch(&[vec![11], vec![11]]);
+            tree.update_leaf(1, &vec![14]).unwrap();
+            assert!(tree.prove(&vec![11]).is_some());
+        }
+    }
+    #[test]
+    fn fallible_construct_append_and_update() {
+        let data = example_data(6);
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "fallible").unwrap();
+        let mut tree = MerkleTree::try_construct(&data, store).unwrap();
+        tree.try_append(&vec![42]).unwrap();
+        let update = GlobalHasher::hash_leaf(&vec![43]);
+        tree.try_update(&GlobalHasher::hash_leaf(&vec![42]), update)
+            .unwrap();
+        assert!(matches!(
+            tree.try_update(&GlobalHasher::hash_leaf(&vec![42]), update),
+            Err(merkle_tree::MerkleError::HashNotFound(_))
+        ));
+        let proof = tree.prove(&vec![43]).unwrap();
+        assert!(MerkleTree::<SledStore>::verify_proof(
+            &vec![43],
+            &proof,
+            tree.root()
+        ));
+    }
+    #[test]
+    fn append_from_an_empty_tree() {
+        let empty: Vec<Vec<u8>> = vec![];
+        let data = example_data(9);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("append_from_empty-{layout:?}"),
+            )
+            .unwrap();
+            let mut tree = MerkleTree::<_>::construct_with_layout(&empty, layout, store);
+            assert_eq!(tree.root(), &MerkleTree::<SledStore>::empty_root());
+            for item in data.iter() {
+                tree.append(item);
+                let proof = tree.prove(item).unwrap();
+                assert!(MerkleTree::<SledStore>::verify_proof(
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("append_from_empty-expected-{layout:?}"),
+            )
+            .unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            assert_eq!(tree.root(), expected.root());
+        }
+    }
+    #[test]
+    fn reopen_a_persisted_tree() {
+        let data = example_data(11);
+        let extra = vec![100];
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let name = format!("reopen-{layout:?}");
+            let store = SledStore::new(LazyLock::force(&SLED_DB), &name).unwrap();
+            let tree = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            let metadata = tree.metadata();
+            drop(tree);
+
+            let store = SledStore::new(LazyLock::force(&SLED_DB), &name).unwrap();
+            let mut tree = MerkleTree::open(store).unwrap();
+            assert_eq!(tree.metadata(), metadata);
+            tree.append(&extra);
+
+            let mut input = data.clone();
+            input.push(extra.clone());
+            let store =
+                SledStore::new(LazyLock::force(&SLED_DB), &format!("{name}-expected")).unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&input, layout, store);
+            assert_eq!(tree.root(), expected.root());
+            let proof = tree.prove(&extra).unwrap();
+            assert!(MerkleTree::<SledStore>::verify_proof(
+                &extra,
+                &proof,
+                tree.root()
+            ));
+        }
+    }
+    #[test]
+    fn opening_with_a_different_hasher_is_an_error() {
+        use merkle_tree::hashers::Rfc6962;
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "reopen-hasher").unwrap();
+        let tree = MerkleTree::construct(example_data(4), store);
+        drop(tree);
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "reopen-hasher").unwrap();
+        assert!(matches!(
+            MerkleTree::<_, Rfc6962>::open_with_hasher(store),
+            Err(merkle_tree::MerkleError::InvalidMetadata(_))
+        ));
+    }
+    #[test]
+    fn roll_back_appends_and_remove_leaves() {
+        use merkle_tree::RemovalMode;
+        let data = example_data(13);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store =
+                SledStore::new(LazyLock::force(&SLED_DB), &format!("truncate-{layout:?}")).unwrap();
+            let mut tree = MerkleTree::<_>::construct_with_layout(&data[..6], layout, store);
+            for item in data[6..].iter() {
+                tree.append(item);
+            }
+            // roll the appends back and add them again
+            tree.truncate(6);
+            assert!(tree.prove(&data[9]).is_none());
+            for item in data[6..].iter() {
+                tree.append(item);
+            }
+            tree.remove(3, RemovalMode::Compact);
+            let mut input = data.clone();
+            input.remove(3);
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("truncate-expected-{layout:?}"),
+            )
+            .unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&input, layout, store);
+            assert_eq!(tree.root(), expected.root());
+            for item in input.iter() {
+                let proof = tree.prove(item).unwrap();
+                assert!(MerkleTree::<SledStore>::verify_proof(
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+        }
+    }
+    #[test]
+    fn append_batch() {
+        let data = example_data(37);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("append_batch-{layout:?}"),
+            )
+            .unwrap();
+            let mut tree = MerkleTree::<_>::construct_with_layout(&data[..5], layout, store);
+            tree.append_batch(&data[5..20]);
+            tree.append_batch(&data[20..]);
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("append_batch-expected-{layout:?}"),
+            )
+            .unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            assert_eq!(tree.root(), expected.root());
+            for item in data.iter() {
+                let proof = tree.prove(item).unwrap();
+                assert!(MerkleTree::<SledStore>::verify_proof(
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+        }
+    }
+    #[test]
+    fn update_many() {
+        let data = example_data(21);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("update_many-{layout:?}"),
+            )
+            .unwrap();
+            let mut tree = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+            let updates = vec![(2, vec![102]), (3, vec![103]), (20, vec![120])];
+            tree.update_many(updates.clone());
+            let mut input = data.clone();
+            for (index, item) in updates {
+                input[index] = item;
+            }
+            let store = SledStore::new(
+                LazyLock::force(&SLED_DB),
+                &format!("update_many-expected-{layout:?}"),
+            )
+            .unwrap();
+            let expected = MerkleTree::<_>::construct_with_layout(&input, layout, store);
+            assert_eq!(tree.root(), expected.root());
+            for item in input.iter() {
+                let proof = tree.prove(item).unwrap();
+                assert!(MerkleTree::<SledStore>::verify_proof(
+                    item,
+                    &proof,
+                    tree.root()
+                ));
+            }
+            assert!(tree.prove(&data[3]).is_none());
+            // the updated last leaf is promoted in the unbalanced layout, its lookup
+            // has to point at the leaf for it to still be found once the tree grows
+            tree.append(&vec![200]);
+            input.push(vec![200]);
+            for (index, item) in input.iter().enumerate() {
+                let proof = tree.prove(item).unwrap();
+                assert_eq!(proof.leaf_index, index);
+            }
+        }
+    }
+    #[test]
+    fn reopen_a_sparse_tree() {
+        use merkle_tree::sparse::{SparseMerkleTree, SparsePath};
+        type Tree = SparseMerkleTree<SledStore<SparsePath>>;
+        let key = |i: u8| GlobalHasher::hash_data(&[i]);
+        let store = SledStore::new_keyed(LazyLock::force(&SLED_DB), "sparse").unwrap();
+        let mut tree = Tree::new(store).unwrap();
+        for i in 0..10 {
+            tree.insert(key(i), &vec![i]);
+        }
+        tree.remove(&key(4));
+        let root = *tree.root();
+        drop(tree);
+        // the sparse tree doesn't look its nodes up by hash
+        let lookups = SLED_DB.open_tree("sparse-lookup").unwrap();
+        assert!(lookups.is_empty());
+
+        let store = SledStore::new_keyed(LazyLock::force(&SLED_DB), "sparse").unwrap();
+        let tree = Tree::new(store).unwrap();
+        assert_eq!(tree.root(), &root);
+        let proof = tree.prove(&key(7));
+        assert!(Tree::verify_inclusion(
+            &key(7),
+            &vec![7],
+            &proof,
+            tree.root()
+        ));
+        let proof = tree.prove(&key(4));
+        assert!(Tree::verify_non_inclusion(&key(4), &proof, tree.root()));
+    }
+    #[test]
+    fn reopen_a_mountain_range() {
+        use merkle_tree::mmr::MerkleMountainRange;
+        let data = example_data(11);
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "mmr").unwrap();
+        let mut range = MerkleMountainRange::new(store).unwrap();
+        for leaf in &data[..7] {
+            range.append(leaf);
+        }
+        let root = *range.root();
+        drop(range);
+
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "mmr").unwrap();
+        let mut range = MerkleMountainRange::<SledStore>::open(store).unwrap();
+        assert_eq!(range.len(), 7);
+        assert_eq!(range.root(), &root);
+        for leaf in &data[7..] {
+            range.append(leaf);
+        }
+        let proof = range.prove(3).unwrap();
+        assert!(MerkleMountainRange::<SledStore>::verify_proof(
+            range.len(),
+            &data[3],
+            &proof,
+            range.root()
+        ));
+    }
+    #[tokio::test]
+    #[cfg(feature = "async")]
+    async fn async_tree_shares_the_store_format() {
+        use merkle_tree::{
+            async_tree::AsyncMerkleTree,
+            stores::{Blocking, TreeCache},
+        };
+        let data = example_data(10);
+        let store = Blocking::new(SledStore::new(LazyLock::force(&SLED_DB), "async").unwrap());
+        let mut tree = AsyncMerkleTree::<_>::construct(data[..6].to_vec(), store.clone())
+            .await
+            .unwrap();
+        tree.append_batch(&data[6..]).await.unwrap();
+        let root = *tree.root();
+        drop(tree);
+
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "async").unwrap();
+        let mut reopened = MerkleTree::<_>::open(store).unwrap();
+        assert_eq!(reopened.root(), &root);
+        assert!(reopened.check_integrity().unwrap().is_ok());
+        assert_eq!(
+            reopened.root(),
+            MerkleTree::<_>::construct(&data, TreeCache::new()).root()
+        );
+        reopened.update_leaf(3, &vec![103]);
+        let root = *reopened.root();
+        drop(reopened);
+
+        let store = Blocking::new(SledStore::new(LazyLock::force(&SLED_DB), "async").unwrap());
+        let tree = AsyncMerkleTree::<_>::open(store).await.unwrap();
+        assert_eq!(tree.root(), &root);
+        let proof = tree.prove(&vec![103]).await.unwrap().unwrap();
+        assert!(MerkleTree::<SledStore>::verify_proof_at(
+            3,
+            16,
+            &vec![103],
+            &proof,
+            &root
+        ));
+    }
+    #[test]
+    fn shared_writes_are_persisted() {
+        use merkle_tree::shared::SharedMerkleTree;
+        let data = example_data(20);
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "shared").unwrap();
+        let shared = SharedMerkleTree::new(MerkleTree::<_>::construct(&data[..7], store));
+        for leaf in &data[7..15] {
+            shared.append(leaf).unwrap();
+        }
+        shared.append_batch(&data[15..]).unwrap();
+        shared.update_leaf(2, &vec![102]).unwrap();
+        let root = shared.root();
+        drop(shared.into_inner());
+
+        let store = SledStore::new(LazyLock::force(&SLED_DB), "shared").unwrap();
+        let tree = MerkleTree::<_>::open(store).unwrap();
+        assert_eq!(tree.root(), &root);
+        let mut input = data.clone();
+        input[2] = vec![102];
+        let expected = MerkleTree::<_>::construct(
+            &input,
+            SledStore::new(LazyLock::force(&SLED_DB), "shared-expected").unwrap(),
+        );
+        assert_eq!(tree.root(), expected.root());
+        // the lookups of replaced nodes are dropped with them
+        assert_eq!(tree.prove(&data[2]), None);
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = tree.prove(leaf).unwrap();
+            assert!(MerkleTree::<SledStore>::verify_proof_at(
+                i, 32, leaf, &proof, &root
+            ));
+        }
+    }
+    #[test]
+    fn reopen_a_versioned_tree() {
+        use merkle_tree::stores::{VersionedPath, VersionedStore};
+        type Store = VersionedStore<SledStore<VersionedPath>>;
+        let data = example_data(12);
+        let sled = || SledStore::new_keyed(LazyLock::force(&SLED_DB), "versioned").unwrap();
+        let mut tree = MerkleTree::<_>::construct(&data[..6], Store::new(sled()).unwrap());
+        let first = *tree.root();
+        tree.append_batch(&data[6..]);
+        tree.update_leaf(0, &vec![100]).unwrap();
+        let root = *tree.root();
+        drop(tree);
+
+        let mut tree = MerkleTree::<_>::open(Store::new(sled()).unwrap()).unwrap();
+        assert_eq!(tree.root(), &root);
+        assert_eq!(tree.versions(), vec![0, 1, 2]);
+        assert_eq!(tree.root_at(0), Some(first));
+        let proof = tree.prove_at(0, 3).unwrap();
+        assert!(MerkleTree::<Store>::verify_proof_at(
+            3, 8, &data[3], &proof, &first
+        ));
+        assert!(tree.prove(&vec![100]).is_some());
+        tree.tree_cache.prune_before(2).unwrap();
+        drop(tree);
+
+        let tree = MerkleTree::<_>::open(Store::new(sled()).unwrap()).unwrap();
+        assert_eq!(tree.versions(), vec![2]);
+        assert_eq!(tree.root_at(0), None);
+        for (i, leaf) in data.iter().enumerate().skip(1) {
+            let proof = tree.prove(leaf).unwrap();
+            assert!(MerkleTree::<Store>::verify_proof_at(
+                i, 16, leaf, &proof, &root
+            ));
+        }
+    }
+    #[test]
+    fn commit_a_transaction() {
+        let data = example_data(20);
+        let name = "transaction";
+        let store = || SledStore::new(LazyLock::force(&SLED_DB), name).unwrap();
+        let mut tree = MerkleTree::<_>::construct(&data[..5], store());
+        let metadata = tree.metadata();
+        let failed = tree.transaction(|tx| {
+            tx.try_append_batch(&data[5..])?;
+            tx.try_update_leaf(30, &vec![130])
+        });
+        assert!(failed.is_err());
+        drop(tree);
+        let mut tree = MerkleTree::<_>::open(store()).unwrap();
+        assert_eq!(tree.metadata(), metadata);
+        assert_eq!(tree.prove(&data[5]), None);
+
+        tree.transaction(|tx| {
+            for leaf in &data[5..9] {
+                tx.try_append(leaf)?;
+            }
+            tx.try_append_batch(&data[9..])?;
+            tx.try_update_leaf(3, &vec![103])
+        })
+        .unwrap();
+        let root = *tree.root();
+        drop(tree);
+
+        let tree = MerkleTree::<_>::open(store()).unwrap();
+        assert_eq!(tree.root(), &root);
+        let mut input = data.clone();
+        input[3] = vec![103];
+        let expected = MerkleTree::<_>::construct(
+            &input,
+            SledStore::new(LazyLock::force(&SLED_DB), "transaction-expected").unwrap(),
+        );
+        assert_eq!(tree.root(), expected.root());
+        assert_eq!(tree.prove(&data[3]), None);
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = tree.prove(leaf).unwrap();
+            assert!(MerkleTree::<SledStore>::verify_proof_at(
+                i, 32, leaf, &proof, &root
+            ));
+        }
+        // every committed node can be found by its hash
+        for (_, node) in tree.tree_cache.entries() {
+            let key = tree.tree_cache.get_key_by_hash(&node.data).unwrap();
+            assert_eq!(
+                tree.tree_cache.get(&key).map(|found| found.data),
+                Some(node.data)
+            );
+        }
+    }
+    #[test]
+    fn repair_a_corrupted_store() {
+        use merkle_tree::Node;
+        let data = example_data(11);
+        let name = "integrity";
+        let store = || SledStore::new(LazyLock::force(&SLED_DB), name).unwrap();
+        let mut tree = MerkleTree::<_>::construct(&data[..6], store());
+        tree.append_batch(&data[6..]);
+        assert!(tree.check_integrity().unwrap().is_ok());
+        let root = *tree.root();
+        let metadata = tree.metadata();
+
+        // a torn write of an interior node, a lost lookup found again by scanning and a stale lookup
+        let interior = tree.path_at(metadata.level_count - 2, 1);
+        let parent = interior.get_parent_path(metadata.lowest_level).unwrap();
+        tree.tree_cache
+            .set(interior, Node::new::<GlobalHasher>(&vec![200], false));
+        let lookups = SLED_DB.open_tree(format!("{name}-lookup")).unwrap();
+        let leaf_hash = |i: usize| GlobalHasher::hash_leaf(&data[i]);
+        lookups.remove(leaf_hash(0)).unwrap();
+        let other = lookups.get(leaf_hash(3)).unwrap().unwrap();
+        lookups.insert(leaf_hash(2), other).unwrap();
+        drop(tree);
+
+        let mut tree = MerkleTree::<_>::open(store()).unwrap();
+        let report = tree.check_integrity().unwrap();
+        assert_eq!(report.mismatched, vec![parent, interior]);
+        let leaf = |i| tree.path_at(metadata.level_count, i);
+        assert_eq!(report.stale_lookups, vec![leaf(2)]);
+        assert!(report.missing.is_empty() && report.unexpected.is_empty());
+        tree.repair().unwrap();
+        drop(tree);
+
+        let tree = MerkleTree::<_>::open(store()).unwrap();
+        assert!(tree.check_integrity().unwrap().is_ok());
+        assert_eq!(tree.root(), &root);
+        for (i, leaf) in data.iter().enumerate() {
+            let proof = tree.prove(leaf).unwrap();
+            assert!(MerkleTree::<SledStore>::verify_proof_at(
+                i, 16, leaf, &proof, &root
+            ));
+        }
+    }
+    #[test]
+    fn mutated_stores_pass_the_integrity_check() {
+        use merkle_tree::RemovalMode;
+        let repeated: Vec<_> = [1_u8, 2, 1, 3, 1].iter().map(|d| vec![*d]).collect();
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            for (name, data) in [("unique", example_data(7)), ("repeated", repeated.clone())] {
+                let name = format!("checked-{name}-{layout:?}");
+                let store = SledStore::new(LazyLock::force(&SLED_DB), &name).unwrap();
+                let mut tree = MerkleTree::<_>::construct_with_layout(&data, layout, store);
+                tree.update_leaf(0, &vec![100]).unwrap();
+                tree.update_leaf(data.len() - 1, &vec![101]).unwrap();
+                assert!(tree.check_integrity().unwrap().is_ok(), "{name}");
+                tree.append(&vec![102]);
+                tree.append_batch(&[vec![103], vec![2]]);
+                assert!(tree.check_integrity().unwrap().is_ok(), "{name}");
+                tree.remove(1, RemovalMode::Compact);
+                tree.remove(tree.len() - 1, RemovalMode::Compact);
+                assert!(tree.check_integrity().unwrap().is_ok(), "{name}");
+            }
+        }
+    }
 }
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod versioned_tree_with_indexmap_store {
+    use indexmap::IndexMap;
+    use merkle_tree::{
+        example_data,
+        stores::{TreeCache, VersionRetention, VersionedPath, VersionedStore},
+        MerkleTree, Node, NodeStore, TreeLayout,
+    };
+    type Store = VersionedStore<IndexMap<VersionedPath, Node>>;
+    type Tree = MerkleTree<Store>;
+    #[test]
+    fn old_versions_stay_provable() {
+        let data = example_data(20);
+        for layout in [TreeLayout::Padded, TreeLayout::Unbalanced] {
+            let store = Store::new(IndexMap::new()).unwrap();
+            let mut tree = Tree::construct_with_layout(&data[..5], layout, store);
+            let mut inputs = vec![data[..5].to_vec()];
+            for leaf in &data[5..12] {
+                tree.append(leaf);
+                inputs.push(
+                    inputs
+                        .last()
+                        .unwrap()
+                        .iter()
+                        .chain([leaf])
+                        .cloned()
+                        .collect(),
+                );
+            }
+            tree.update_leaf(2, &vec![102]).unwrap();
+            let mut updated = inputs.last().unwrap().clone();
+            updated[2] = vec![102];
+            inputs.push(updated);
+            tree.append_batch(&data[12..]);
+            inputs.push(
+                inputs
+                    .last()
+                    .unwrap()
+                    .iter()
+                    .chain(&data[12..])
+                    .cloned()
+                    .collect(),
+            );
+
+            assert_eq!(
+                tree.versions(),
+                (0..inputs.len() as u64).collect::<Vec<_>>()
+            );
+            assert_eq!(tree.version(), Some(inputs.len() as u64 - 1));
+            for (version, input) in inputs.iter().enumerate() {
+                let version = version as u64;
+                let expected =
+                    MerkleTree::<_>::construct_with_layout(input, layout, TreeCache::new());
+                let root = tree.root_at(version).unwrap();
+                assert_eq!(&root, expected.root(), "{layout:?} {version}");
+                let metadata = tree.metadata_at(version).unwrap().unwrap();
+                assert_eq!(metadata.leaf_count, expected.metadata().leaf_count);
+                for (i, leaf) in input.iter().enumerate() {
+                    let proof = tree.prove_at(version, i).unwrap();
+                    assert_eq!(
+                        proof.sibling_hashes(),
+                        expected.prove_index(i).unwrap().sibling_hashes()
+                    );
+                    assert!(Tree::verify_proof_at(
+                        i,
+                        proof.leaf_count,
+                        leaf,
+                        &proof,
+                        &root
+                    ));
+                }
+                assert_eq!(tree.prove_at(version, metadata.leaf_count), None);
+            }
+            assert_eq!(tree.root_at(inputs.len() as u64), None);
+            // the latest version is the tree itself
+            assert_eq!(
+                tree.root_at(tree.version().unwrap()).as_ref(),
+                Some(tree.root())
+            );
+            assert_eq!(tree.prove(&data[2]), None);
+            assert!(tree.prove(&vec![102]).is_some());
+        }
+    }
+    #[test]
+    fn pruning_keeps_the_retained_versions() {
+        let data = example_data(16);
+        let store = Store::new(IndexMap::new()).unwrap();
+        let mut tree = Tree::construct(&data[..4], store);
+        let mut roots = vec![*tree.root()];
+        for (i, leaf) in data[4..].iter().enumerate() {
+            tree.append(leaf);
+            tree.update_leaf(i, &vec![100 + i as u8]).unwrap();
+            roots.push(*tree.root());
+        }
+        let nodes = tree.tree_cache.entries().count();
+        tree.tree_cache.prune_before(10).unwrap();
+        assert_eq!(tree.versions(), (10..=24).collect::<Vec<_>>());
+        assert_eq!(tree.root_at(9), None);
+        assert_eq!(tree.prove_at(9, 0), None);
+        // the nodes of the latest version are untouched
+        assert_eq!(tree.tree_cache.entries().count(), nodes);
+        for version in tree.versions() {
+            let root = tree.root_at(version).unwrap();
+            // the padding copies of the last leaf aren't proven
+            let len = tree.metadata_at(version).unwrap().unwrap().padding_start + 1;
+            for i in 0..len {
+                let proof = tree.prove_at(version, i).unwrap();
+                assert_eq!(proof.root, root);
+            }
+        }
+
+        // a retention policy prunes as versions are completed
+        let store = Store::with_retention(IndexMap::new(), VersionRetention::Last(3)).unwrap();
+        let mut tree = Tree::construct(&data[..4], store);
+        for (i, leaf) in data[4..].iter().enumerate() {
+            tree.append(leaf);
+            tree.update_leaf(i, &vec![100 + i as u8]).unwrap();
+        }
+        assert_eq!(tree.versions(), vec![22, 23, 24]);
+        assert_eq!(tree.root_at(22), Some(roots[11]));
+        assert_eq!(tree.root_at(24), Some(roots[12]));
+        for version in 22..=24 {
+            assert!(tree.prove_at(version, 14).is_some());
+        }
+        assert!(tree.prove_at(21, 0).is_none());
+    }
+}